@@ -0,0 +1,178 @@
+//! Turns the listing format `disassemble_chunk` prints back into a chunk, so
+//! VM behaviour can be tested without going through the compiler:
+//!
+//! ```text
+//! == <script> ==
+//! 0000    1 OP_CONSTANT         0 '1.2'
+//! 0002    | OP_NEGATE
+//! 0003    | OP_PRINT
+//! 0004    2 OP_RETURN
+//! ```
+//!
+//! The offset and line columns are optional; without them each instruction
+//! gets the line of the listing it appears on. A constant's index may be
+//! left out too, in which case the constant is appended to the pool.
+//! Strings are double-quoted with the same escapes as Lox source; numbers,
+//! `true`, `false` and `nil` are single-quoted. Variable names may be
+//! single-quoted too. `OP_GET_GLOBAL_ADD_CONSTANT` takes a name and then a
+//! constant. Blank lines, `==` headers and `;` comments are skipped.
+
+use crate::chunk::{init_chunk, operand_count, write_chunk, Chunk, OP_ADD, OP_CONSTANT, OP_DEFINE_GLOBAL, OP_DIVIDE, OP_EQUAL, OP_FALSE, OP_GET_GLOBAL, OP_GREATER, OP_LESS, OP_MULTIPLY, OP_NEGATE, OP_NIL, OP_NOT, OP_POP, OP_PRINT, OP_RETURN, OP_SET_GLOBAL, OP_SUBTRACT, OP_TO_STRING, OP_TRUE, OP_NOT_EQUAL, OP_GREATER_EQUAL, OP_LESS_EQUAL, OP_GET_GLOBAL_ADD_CONSTANT};
+use crate::compiler::unescape;
+use crate::debug::constant_literal;
+use crate::error::AssemblyError;
+use crate::scanner::Span;
+use crate::value::{bool_val, number_val, string_val, Value, NIL_VAL};
+
+const OPCODES: &[(&str, usize)] = &[
+    ("OP_CONSTANT", OP_CONSTANT),
+    ("OP_RETURN", OP_RETURN),
+    ("OP_NEGATE", OP_NEGATE),
+    ("OP_ADD", OP_ADD),
+    ("OP_SUBTRACT", OP_SUBTRACT),
+    ("OP_MULTIPLY", OP_MULTIPLY),
+    ("OP_DIVIDE", OP_DIVIDE),
+    ("OP_NIL", OP_NIL),
+    ("OP_TRUE", OP_TRUE),
+    ("OP_FALSE", OP_FALSE),
+    ("OP_NOT", OP_NOT),
+    ("OP_EQUAL", OP_EQUAL),
+    ("OP_GREATER", OP_GREATER),
+    ("OP_LESS", OP_LESS),
+    ("OP_GET_GLOBAL", OP_GET_GLOBAL),
+    ("OP_TO_STRING", OP_TO_STRING),
+    ("OP_PRINT", OP_PRINT),
+    ("OP_POP", OP_POP),
+    ("OP_DEFINE_GLOBAL", OP_DEFINE_GLOBAL),
+    ("OP_SET_GLOBAL", OP_SET_GLOBAL),
+    ("OP_NOT_EQUAL", OP_NOT_EQUAL),
+    ("OP_GREATER_EQUAL", OP_GREATER_EQUAL),
+    ("OP_LESS_EQUAL", OP_LESS_EQUAL),
+    ("OP_GET_GLOBAL_ADD_CONSTANT", OP_GET_GLOBAL_ADD_CONSTANT),
+];
+
+pub fn assemble(text: &str) -> Result<Chunk, AssemblyError> {
+    let mut chunk = init_chunk();
+    let mut constants: Vec<Option<Value>> = Vec::new();
+    let mut previous_line = 0;
+
+    for (idx, text_line) in text.lines().enumerate() {
+        let line_number = idx + 1;
+        let error = |message: String| AssemblyError { line: line_number, message };
+
+        let trimmed = text_line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("==") || trimmed.starts_with(';') { continue; }
+
+        let op_start = trimmed.find("OP_").ok_or_else(|| error("Expect an instruction.".to_string()))?;
+        let line = match trimmed[..op_start].split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] => line_number as i32,
+            [_offset, "|"] => previous_line,
+            [_offset, line] => line.parse().map_err(|_| error(format!("Invalid line number '{}'.", line)))?,
+            _ => return Err(error("Expect an offset and a line number before the instruction.".to_string())),
+        };
+        previous_line = line;
+
+        let rest = &trimmed[op_start..];
+        let name_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (name, operand) = (&rest[..name_end], rest[name_end..].trim());
+        let opcode = OPCODES.iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, opcode)| *opcode)
+            .ok_or_else(|| error(format!("Unknown instruction '{}'.", name)))?;
+
+        write_chunk(&mut chunk, opcode, line, 1, Span::default());
+
+        let names_variable = matches!(opcode, OP_GET_GLOBAL | OP_SET_GLOBAL | OP_DEFINE_GLOBAL | OP_GET_GLOBAL_ADD_CONSTANT);
+        let operands = operand_count(opcode).unwrap_or(0);
+        if operands == 0 {
+            if !operand.is_empty() {
+                return Err(error(format!("{} takes no operand.", name)));
+            }
+            continue;
+        }
+
+        let mut operand = operand;
+        for position in 0..operands {
+            let quote = operand.find(['\'', '"'])
+                .ok_or_else(|| error(format!("Expect a quoted constant after {}.", name)))?;
+            let index = operand[..quote].trim();
+            let (value, length) = if operand[quote..].starts_with('"') {
+                let length = string_length(&operand[quote..])
+                    .ok_or_else(|| error("Unterminated string; expect a closing \".".to_string()))?;
+                let chars = unescape(&operand[quote + 1..quote + length - 1]).map_err(error)?;
+                (string_val(&chars), length)
+            }
+            else {
+                let close = operand[quote + 1..].find('\'')
+                    .ok_or_else(|| error("Unterminated constant; expect a closing '.".to_string()))?;
+                let contents = &operand[quote + 1..quote + 1 + close];
+                // Variable names are always strings, so they may be
+                // single-quoted too.
+                let value = if names_variable && position == 0 {
+                    string_val(contents)
+                }
+                else {
+                    parse_constant(contents)
+                        .ok_or_else(|| error(format!("Invalid constant '{}'; write strings in double quotes.", contents)))?
+                };
+                (value, close + 2)
+            };
+            operand = operand[quote + length..].trim_start();
+
+            let index = if index.is_empty() {
+                constants.push(Some(value));
+                constants.len() - 1
+            }
+            else {
+                let index: usize = index.parse().map_err(|_| error(format!("Invalid constant index '{}'.", index)))?;
+                if index > u8::MAX as usize {
+                    return Err(error(format!("Constant index {} is too large.", index)));
+                }
+                if constants.len() <= index { constants.resize(index + 1, None); }
+                match &constants[index] {
+                    Some(existing) if existing.to_string() != value.to_string() || existing.value_type() != value.value_type() => {
+                        return Err(error(format!("Constant {} is already {}.", index, constant_literal(existing))));
+                    }
+                    _ => constants[index] = Some(value),
+                }
+                index
+            };
+            write_chunk(&mut chunk, index, line, 1, Span::default());
+        }
+        if !operand.is_empty() {
+            return Err(error(format!("Unexpected '{}' after the operands of {}.", operand, name)));
+        }
+    }
+
+    for (index, constant) in constants.into_iter().enumerate() {
+        let undefined = || AssemblyError { line: text.lines().count(), message: format!("Constant {} is never defined.", index) };
+        let constant = constant.ok_or_else(undefined)?;
+        chunk.constants.push(constant);
+    }
+    Ok(chunk)
+}
+
+/// Reads a single-quoted constant: a number, `true`, `false` or `nil`.
+fn parse_constant(contents: &str) -> Option<Value> {
+    match contents {
+        "nil" => Some(NIL_VAL),
+        "true" => Some(bool_val(true)),
+        "false" => Some(bool_val(false)),
+        _ => contents.parse::<f64>().ok().map(number_val),
+    }
+}
+
+/// The length in bytes of the double-quoted string `text` starts with,
+/// quotes included, skipping over escaped characters.
+fn string_length(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(idx + 1),
+            _ => {}
+        }
+    }
+    None
+}
@@ -0,0 +1,105 @@
+//! A typed syntax tree for Lox programs, built by `ast_parser::parse` from
+//! the same tokens the single-pass compiler reads and lowered to bytecode by
+//! `codegen::generate`. Nodes keep the tokens they came from, so tools have
+//! each construct's line, column and span.
+
+use crate::scanner::{Span, Token};
+use crate::value::Value;
+
+/// Which front end `Vm` compiles source with. Both produce the same
+/// bytecode and report the same errors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrontEnd {
+    /// The Pratt parser in `compiler`, which emits code as it parses.
+    #[default]
+    SinglePass,
+    /// `ast_parser::parse` followed by `codegen::generate`.
+    Ast,
+}
+
+#[derive(Clone, Debug)]
+pub struct Program {
+    pub declarations: Vec<Declaration>,
+    /// The `EOF` token.
+    pub end: Token,
+}
+
+#[derive(Clone, Debug)]
+pub enum Declaration {
+    /// `var name = initializer;`, where `keyword` is the `var` token.
+    Var { keyword: Token, name: Identifier, initializer: Option<Expr>, semicolon: Token, span: Span },
+    Statement(Stmt),
+}
+
+#[derive(Clone, Debug)]
+pub enum Stmt {
+    Print { keyword: Token, value: Expr, span: Span },
+    Expression { expression: Expr, semicolon: Token, span: Span },
+}
+
+#[derive(Clone, Debug)]
+pub struct Identifier {
+    pub name: String,
+    pub token: Token,
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    /// A number, string, `true`, `false` or `nil` literal.
+    Literal { value: Value, token: Token },
+    /// `"a ${x} b"`: each part is the literal text before an expression,
+    /// followed by the closing string segment.
+    Interpolation { parts: Vec<(StringSegment, Expr)>, tail: StringSegment },
+    Variable(Identifier),
+    Assign { name: Identifier, value: Box<Expr> },
+    Unary { operator: Token, operand: Box<Expr> },
+    Binary { left: Box<Expr>, operator: Token, right: Box<Expr> },
+    Grouping { expression: Box<Expr>, span: Span },
+}
+
+/// A piece of an interpolated string with its escapes decoded.
+#[derive(Clone, Debug)]
+pub struct StringSegment {
+    pub text: String,
+    pub token: Token,
+}
+
+impl Declaration {
+    pub fn span(&self) -> Span {
+        match self {
+            Declaration::Var { span, .. } => *span,
+            Declaration::Statement(statement) => statement.span(),
+        }
+    }
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Print { span, .. } | Stmt::Expression { span, .. } => *span,
+        }
+    }
+}
+
+impl Expr {
+    /// The source range the expression was parsed from.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Literal { token, .. } => token.span,
+            Expr::Interpolation { parts, tail } => {
+                let start = parts.first().map_or(tail.token.span, |(segment, _)| segment.token.span);
+                join(start, tail.token.span)
+            }
+            Expr::Variable(name) => name.token.span,
+            Expr::Assign { name, value } => join(name.token.span, value.span()),
+            Expr::Unary { operator, operand } => join(operator.span, operand.span()),
+            Expr::Binary { left, right, .. } => join(left.span(), right.span()),
+            Expr::Grouping { span, .. } => *span,
+        }
+    }
+}
+
+/// The span from the start of `first` to the end of `last`.
+pub fn join(first: Span, last: Span) -> Span {
+    Span { start: first.start, end: last.end }
+}
@@ -0,0 +1,282 @@
+//! Builds an `ast::Program` from source. It follows the single-pass
+//! compiler's grammar, precedence and error recovery exactly, so the two
+//! front ends report the same errors for the same input.
+
+use crate::ast::{join, Declaration, Expr, Identifier, Program, Stmt, StringSegment};
+use crate::compiler::{parse_number, unescape};
+use crate::error::CompileError;
+use crate::parser::{PREC_ASSIGNMENT, PREC_COMPARISON, PREC_EQUALITY, PREC_FACTOR, PREC_NONE, PREC_TERM, PREC_UNARY};
+use crate::scanner::{make_empty_token, Edition, Scanner, Span, Token, TokenType};
+use crate::value::{bool_val, number_val, string_val, NIL_VAL};
+
+/// Parses `source`, returning every syntax error if there are any.
+pub fn parse(source: &str, edition: Edition) -> Result<Program, Vec<CompileError>> {
+    let mut parser = AstParser {
+        source,
+        scanner: Scanner::with_edition(source.to_string(), edition),
+        current: make_empty_token(),
+        previous: make_empty_token(),
+        panic_mode: false,
+        errors: Vec::new(),
+    };
+
+    parser.advance();
+    let mut declarations = Vec::new();
+    while !parser.match_token(TokenType::EOF) {
+        declarations.push(parser.declaration());
+    }
+
+    if parser.errors.is_empty() {
+        Ok(Program { declarations, end: parser.previous })
+    }
+    else {
+        Err(parser.errors)
+    }
+}
+
+struct AstParser<'a> {
+    source: &'a str,
+    scanner: Scanner,
+    current: Token,
+    previous: Token,
+    panic_mode: bool,
+    errors: Vec<CompileError>,
+}
+
+impl<'a> AstParser<'a> {
+    fn advance(&mut self) {
+        self.previous = self.current;
+
+        loop {
+            self.current = self.scanner.scan_token();
+            if self.current.token_type != TokenType::Error { break; }
+            self.error_at(self.current, self.current.message.to_string());
+        }
+    }
+
+    fn error(&mut self, message: &str) {
+        self.error_at(self.previous, message.to_string());
+    }
+
+    fn error_at(&mut self, token: Token, message: String) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        self.errors.push(CompileError {
+            line: token.line,
+            column: token.column,
+            span: token.span,
+            lexeme: token.text(self.source).to_string(),
+            message,
+            token_type: token.token_type,
+        });
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) {
+        if self.current.token_type == token_type {
+            self.advance();
+            return;
+        }
+        self.error_at(self.current, message.to_string());
+    }
+
+    fn match_token(&mut self, token_type: TokenType) -> bool {
+        if self.current.token_type != token_type { return false; }
+        self.advance();
+        true
+    }
+
+    /// The span from `start` to the end of the last token consumed.
+    fn span_from(&self, start: Token) -> Span {
+        join(start.span, self.previous.span)
+    }
+
+    fn declaration(&mut self) -> Declaration {
+        let declaration = if self.match_token(TokenType::Var) {
+            self.var_declaration()
+        }
+        else {
+            Declaration::Statement(self.statement())
+        };
+
+        if self.panic_mode { self.synchronize(); }
+        declaration
+    }
+
+    fn var_declaration(&mut self) -> Declaration {
+        let keyword = self.previous;
+        self.consume(TokenType::Identifier, "Expect variable name.");
+        let name = self.identifier(self.previous);
+
+        let initializer = if self.match_token(TokenType::Equal) { Some(self.expression()) } else { None };
+        self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.");
+
+        Declaration::Var { keyword, name, initializer, semicolon: self.previous, span: self.span_from(keyword) }
+    }
+
+    fn statement(&mut self) -> Stmt {
+        if self.match_token(TokenType::Print) {
+            let keyword = self.previous;
+            let value = self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after value.");
+            Stmt::Print { keyword, value, span: self.span_from(keyword) }
+        }
+        else {
+            let expression = self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after expression.");
+            let span = join(expression.span(), self.previous.span);
+            Stmt::Expression { expression, semicolon: self.previous, span }
+        }
+    }
+
+    /// Skips tokens until a likely statement boundary after a syntax error.
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+
+        while self.current.token_type != TokenType::EOF {
+            if self.previous.token_type == TokenType::Semicolon { return; }
+            match self.current.token_type {
+                TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::For |
+                TokenType::If | TokenType::While | TokenType::Print | TokenType::Return => return,
+                _ => {},
+            }
+            self.advance();
+        }
+    }
+
+    fn expression(&mut self) -> Expr {
+        self.parse_precedence(PREC_ASSIGNMENT)
+    }
+
+    fn parse_precedence(&mut self, precedence: i32) -> Expr {
+        self.advance();
+        let can_assign = precedence <= PREC_ASSIGNMENT;
+        let mut expr = match self.previous.token_type {
+            TokenType::LeftParen => self.grouping(),
+            TokenType::Minus | TokenType::Bang => self.unary(),
+            TokenType::Number => self.number(),
+            TokenType::String => self.string(),
+            TokenType::Interpolation => self.interpolation(),
+            TokenType::Identifier => self.variable(can_assign),
+            TokenType::True => Expr::Literal { value: bool_val(true), token: self.previous },
+            TokenType::False => Expr::Literal { value: bool_val(false), token: self.previous },
+            TokenType::Nil => Expr::Literal { value: NIL_VAL, token: self.previous },
+            _ => {
+                self.error("Expect expression.");
+                return Expr::Literal { value: NIL_VAL, token: self.previous };
+            }
+        };
+
+        while precedence <= infix_precedence(self.current.token_type) {
+            self.advance();
+            let operator = self.previous;
+            let right = self.parse_precedence(infix_precedence(operator.token_type) + 1);
+            expr = Expr::Binary { left: Box::new(expr), operator, right: Box::new(right) };
+        }
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.error("Invalid assignment target.");
+        }
+        expr
+    }
+
+    fn grouping(&mut self) -> Expr {
+        let open = self.previous;
+        let expression = self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after expression.");
+        Expr::Grouping { expression: Box::new(expression), span: self.span_from(open) }
+    }
+
+    fn unary(&mut self) -> Expr {
+        let operator = self.previous;
+        let operand = self.parse_precedence(PREC_UNARY);
+        Expr::Unary { operator, operand: Box::new(operand) }
+    }
+
+    fn number(&mut self) -> Expr {
+        let token = self.previous;
+        let value = match parse_number(token.text(self.source)) {
+            Some(number) if number.is_infinite() => {
+                self.error("Number literal is too large.");
+                NIL_VAL
+            }
+            Some(number) => number_val(number),
+            None => {
+                self.error("Invalid number literal.");
+                NIL_VAL
+            }
+        };
+        Expr::Literal { value, token }
+    }
+
+    fn string(&mut self) -> Expr {
+        let segment = self.segment(1);
+        Expr::Literal { value: string_val(&segment.text), token: segment.token }
+    }
+
+    /// Parses `"a ${x} b ${y} c"` into its literal segments and the
+    /// expressions between them.
+    fn interpolation(&mut self) -> Expr {
+        let mut parts = Vec::new();
+        loop {
+            let segment = self.segment(2);
+            let expression = self.expression();
+            parts.push((segment, expression));
+
+            match self.current.token_type {
+                TokenType::Interpolation => self.advance(),
+                TokenType::String => break,
+                _ => {
+                    self.error_at(self.current, "Expect '}' after interpolated expression.".to_string());
+                    let tail = StringSegment { text: String::new(), token: self.current };
+                    return Expr::Interpolation { parts, tail };
+                }
+            }
+        }
+        self.advance();
+        let tail = self.segment(1);
+        Expr::Interpolation { parts, tail }
+    }
+
+    /// Decodes the previous string token, dropping its opening delimiter and
+    /// `close` bytes of closing delimiter.
+    fn segment(&mut self, close: usize) -> StringSegment {
+        let token = self.previous;
+        let text = token.text(self.source);
+        let text = match unescape(&text[1..text.len() - close]) {
+            Ok(text) => text,
+            Err(message) => {
+                self.error(&message);
+                String::new()
+            }
+        };
+        StringSegment { text, token }
+    }
+
+    fn variable(&mut self, can_assign: bool) -> Expr {
+        let name = self.identifier(self.previous);
+        if can_assign && self.match_token(TokenType::Equal) {
+            let value = self.expression();
+            Expr::Assign { name, value: Box::new(value) }
+        }
+        else {
+            Expr::Variable(name)
+        }
+    }
+
+    fn identifier(&self, token: Token) -> Identifier {
+        Identifier { name: token.text(self.source).to_string(), token }
+    }
+}
+
+/// The precedence of `token_type` as a binary operator, or `PREC_NONE`.
+fn infix_precedence(token_type: TokenType) -> i32 {
+    match token_type {
+        TokenType::Minus | TokenType::Plus => PREC_TERM,
+        TokenType::Slash | TokenType::Star => PREC_FACTOR,
+        TokenType::BangEqual | TokenType::EqualEqual => PREC_EQUALITY,
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => PREC_COMPARISON,
+        _ => PREC_NONE,
+    }
+}
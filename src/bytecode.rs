@@ -0,0 +1,172 @@
+//! The `.loxc` file format: a compiled chunk written out so it can be run
+//! without recompiling its source.
+//!
+//! All integers are little-endian. A file is laid out as
+//!
+//! ```text
+//! magic      b"LOXC"
+//! version    u16
+//! constants  u32 count, then per constant a u8 tag and its payload:
+//!            0 nil | 1 bool (u8) | 2 number (f64) | 3 string (u32 length, UTF-8 bytes)
+//! code       u32 count, then one u8 per byte of code
+//! lines      per byte of code, its line (i32) and column (u32)
+//! ```
+//!
+//! Spans aren't stored: they point into source text the file doesn't carry.
+
+use std::convert::TryFrom;
+
+use crate::chunk::{init_chunk, write_chunk, Chunk};
+use crate::error::BytecodeError;
+use crate::object::ObjType;
+use crate::scanner::Span;
+use crate::value::{as_bool, as_number, as_obj, bool_val, number_val, string_val, Value, NIL_VAL};
+
+pub const MAGIC: &[u8; 4] = b"LOXC";
+
+/// Bumped whenever the layout or the opcode numbering changes, so files
+/// written by another version are rejected instead of misread.
+pub const FORMAT_VERSION: u16 = 2;
+
+const TAG_NIL: u8 = 0;
+const TAG_BOOL: u8 = 1;
+const TAG_NUMBER: u8 = 2;
+const TAG_STRING: u8 = 3;
+
+pub fn serialize(chunk: &Chunk) -> Result<Vec<u8>, BytecodeError> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+    write_u32(&mut bytes, chunk.constants.len())?;
+    for constant in &chunk.constants {
+        write_constant(&mut bytes, constant)?;
+    }
+
+    write_u32(&mut bytes, chunk.code.len())?;
+    for byte in &chunk.code {
+        let byte = u8::try_from(*byte)
+            .map_err(|_| error(format!("Code byte {} doesn't fit in a byte.", byte)))?;
+        bytes.push(byte);
+    }
+    for (line, column) in chunk.lines.iter().zip(&chunk.columns) {
+        bytes.extend_from_slice(&line.to_le_bytes());
+        write_u32(&mut bytes, *column)?;
+    }
+    Ok(bytes)
+}
+
+/// Reads a chunk back from `bytes`. Only the file's structure is checked
+/// here; `Vm::run_chunk` verifies the code before running it.
+pub fn deserialize(bytes: &[u8]) -> Result<Chunk, BytecodeError> {
+    let mut reader = Reader { bytes, offset: 0 };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(error("Not an rlox bytecode file.".to_string()));
+    }
+    let version = u16::from_le_bytes(reader.array()?);
+    if version != FORMAT_VERSION {
+        return Err(error(format!("Unsupported bytecode version {} (expected {}).", version, FORMAT_VERSION)));
+    }
+
+    let mut chunk = init_chunk();
+    for _ in 0..reader.u32()? {
+        let constant = reader.constant()?;
+        chunk.constants.push(constant);
+    }
+
+    let length = reader.u32()? as usize;
+    let code = reader.take(length)?;
+    for byte in code {
+        let line = i32::from_le_bytes(reader.array()?);
+        let column = reader.u32()? as usize;
+        write_chunk(&mut chunk, *byte as usize, line, column, Span::default());
+    }
+
+    if reader.offset != bytes.len() {
+        return Err(error("Unexpected data after the end of the bytecode.".to_string()));
+    }
+    Ok(chunk)
+}
+
+fn error(message: String) -> BytecodeError {
+    BytecodeError { message }
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: usize) -> Result<(), BytecodeError> {
+    let value = u32::try_from(value).map_err(|_| error(format!("{} is too large to serialize.", value)))?;
+    bytes.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+fn write_constant(bytes: &mut Vec<u8>, constant: &Value) -> Result<(), BytecodeError> {
+    if let Some(boolean) = as_bool(constant) {
+        bytes.push(TAG_BOOL);
+        bytes.push(boolean as u8);
+    }
+    else if let Some(number) = as_number(constant) {
+        bytes.push(TAG_NUMBER);
+        bytes.extend_from_slice(&number.to_le_bytes());
+    }
+    else if let Some(obj) = as_obj(constant) {
+        match &obj.t {
+            ObjType::ObjString(chars) => {
+                bytes.push(TAG_STRING);
+                write_u32(bytes, chars.len())?;
+                bytes.extend_from_slice(chars.as_bytes());
+            }
+            ObjType::ObjNative { name, .. } => {
+                return Err(error(format!("Native function '{}' can't be serialized.", name)));
+            }
+        }
+    }
+    else {
+        bytes.push(TAG_NIL);
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], BytecodeError> {
+        let end = self.offset.checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| error("Truncated bytecode file.".to_string()))?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], BytecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u32(&mut self) -> Result<u32, BytecodeError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn constant(&mut self) -> Result<Value, BytecodeError> {
+        let [tag] = self.array()?;
+        match tag {
+            TAG_NIL => Ok(NIL_VAL),
+            TAG_BOOL => {
+                let [boolean] = self.array()?;
+                Ok(bool_val(boolean != 0))
+            }
+            TAG_NUMBER => Ok(number_val(f64::from_le_bytes(self.array()?))),
+            TAG_STRING => {
+                let length = self.u32()? as usize;
+                let chars = std::str::from_utf8(self.take(length)?)
+                    .map_err(|_| error("String constant is not valid UTF-8.".to_string()))?;
+                Ok(string_val(chars))
+            }
+            _ => Err(error(format!("Unknown constant tag {}.", tag))),
+        }
+    }
+}
@@ -1,5 +1,7 @@
-use crate::value::Value;
+use crate::scanner::Span;
+use crate::value::{as_number, as_string, Value, ValueType};
 
+// Opcode numbers are part of the `.loxc` format; see `bytecode::FORMAT_VERSION`.
 pub const OP_RETURN: usize = 1;
 pub const OP_CONSTANT: usize = 0;
 pub const OP_NEGATE: usize = 2;
@@ -14,27 +16,75 @@ pub const OP_NOT: usize = 10;
 pub const OP_EQUAL: usize = 11;
 pub const OP_GREATER: usize = 12;
 pub const OP_LESS: usize = 13;
+pub const OP_GET_GLOBAL: usize = 14;
+pub const OP_TO_STRING: usize = 15;
+pub const OP_PRINT: usize = 16;
+pub const OP_POP: usize = 17;
+pub const OP_DEFINE_GLOBAL: usize = 18;
+pub const OP_SET_GLOBAL: usize = 19;
+pub const OP_NOT_EQUAL: usize = 20;
+pub const OP_GREATER_EQUAL: usize = 21;
+pub const OP_LESS_EQUAL: usize = 22;
+/// `OP_GET_GLOBAL`, `OP_CONSTANT` and `OP_ADD` in one instruction, with the
+/// variable name and the constant as its two operands.
+pub const OP_GET_GLOBAL_ADD_CONSTANT: usize = 23;
 
+/// How many operand bytes follow `opcode`, or `None` if it isn't one.
+pub fn operand_count(opcode: usize) -> Option<usize> {
+    match opcode {
+        OP_CONSTANT | OP_GET_GLOBAL | OP_SET_GLOBAL | OP_DEFINE_GLOBAL => Some(1),
+        OP_GET_GLOBAL_ADD_CONSTANT => Some(2),
+        OP_RETURN | OP_NEGATE | OP_ADD | OP_SUBTRACT | OP_MULTIPLY | OP_DIVIDE | OP_NIL | OP_TRUE |
+        OP_FALSE | OP_NOT | OP_EQUAL | OP_GREATER | OP_LESS | OP_TO_STRING | OP_PRINT | OP_POP |
+        OP_NOT_EQUAL | OP_GREATER_EQUAL | OP_LESS_EQUAL => Some(0),
+        _ => None,
+    }
+}
+
+/// Bytecode plus its debug info: `lines`, `columns` and `spans` hold the
+/// source location of the token each byte in `code` was compiled from.
 pub struct Chunk {
     pub code: Vec<usize>,
     pub lines: Vec<i32>,
+    pub columns: Vec<usize>,
+    pub spans: Vec<Span>,
     pub constants: Vec<Value>,
 }
 
 pub const fn init_chunk() -> Chunk {
-    return Chunk {
+    Chunk {
         code: Vec::new(),
         lines: Vec::new(),
+        columns: Vec::new(),
+        spans: Vec::new(),
         constants: Vec::new(),
     }
 }
 
-pub fn write_chunk(chunk: &mut Chunk, byte: usize, line: i32) {
+pub fn write_chunk(chunk: &mut Chunk, byte: usize, line: i32, column: usize, span: Span) {
     chunk.code.push(byte);
     chunk.lines.push(line);
+    chunk.columns.push(column);
+    chunk.spans.push(span);
 }
 
+/// Returns the index of `value` in the constant pool, adding it unless an
+/// identical number or string is already there. Numbers are compared by
+/// bit pattern, so `-0.0` and `0.0` stay distinct and a NaN is shared only
+/// with the same NaN.
 pub fn add_constant(chunk: &mut Chunk, value: Value) -> usize {
+    let existing = chunk.constants.iter().position(|constant| same_constant(constant, &value));
+    if let Some(index) = existing {
+        return index;
+    }
     chunk.constants.push(value);
-    return chunk.constants.len() - 1;
-}
\ No newline at end of file
+    chunk.constants.len() - 1
+}
+
+fn same_constant(a: &Value, b: &Value) -> bool {
+    match (a.value_type(), b.value_type()) {
+        (ValueType::Number, ValueType::Number) => as_number(a).map(f64::to_bits) == as_number(b).map(f64::to_bits),
+        (ValueType::Obj, ValueType::Obj) => as_string(a).is_some() && as_string(a) == as_string(b),
+        _ => false,
+    }
+}
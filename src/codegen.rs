@@ -0,0 +1,199 @@
+//! Lowers an `ast::Program` to a chunk. The bytecode, constant pool and
+//! debug info match what the single-pass compiler emits for the same
+//! source, constant folding and peephole pass included.
+
+use crate::ast::{Declaration, Expr, Identifier, Program, Stmt, StringSegment};
+use crate::chunk::{add_constant, init_chunk, Chunk, OP_ADD, OP_CONSTANT, OP_DEFINE_GLOBAL, OP_DIVIDE, OP_EQUAL, OP_FALSE, OP_GET_GLOBAL, OP_GREATER, OP_GREATER_EQUAL, OP_LESS, OP_LESS_EQUAL, OP_MULTIPLY, OP_NEGATE, OP_NIL, OP_NOT, OP_NOT_EQUAL, OP_POP, OP_PRINT, OP_RETURN, OP_SET_GLOBAL, OP_SUBTRACT, OP_TO_STRING, OP_TRUE, write_chunk};
+use crate::compiler::{fold_binary, fold_unary};
+use crate::error::CompileError;
+use crate::peephole::optimize;
+use crate::scanner::{Token, TokenType};
+use crate::value::{as_bool, string_val, Value, ValueType};
+
+/// Generates code for `program`, which was parsed from `source`.
+pub fn generate(program: &Program, source: &str) -> Result<Chunk, Vec<CompileError>> {
+    let mut generator = Generator { source, chunk: init_chunk(), errors: Vec::new(), failed: false };
+    for declaration in &program.declarations {
+        generator.failed = false;
+        generator.declaration(declaration);
+    }
+    generator.emit(OP_RETURN, program.end);
+
+    if !generator.errors.is_empty() {
+        return Err(generator.errors);
+    }
+    optimize(&mut generator.chunk);
+    Ok(generator.chunk)
+}
+
+struct Generator<'a> {
+    source: &'a str,
+    chunk: Chunk,
+    errors: Vec<CompileError>,
+    /// Whether the current declaration has already reported an error.
+    failed: bool,
+}
+
+impl<'a> Generator<'a> {
+    fn emit(&mut self, byte: usize, token: Token) {
+        write_chunk(&mut self.chunk, byte, token.line, token.column, token.span);
+    }
+
+    fn error(&mut self, token: Token, message: &str) {
+        if self.failed { return; }
+        self.failed = true;
+        self.errors.push(CompileError {
+            line: token.line,
+            column: token.column,
+            span: token.span,
+            lexeme: token.text(self.source).to_string(),
+            message: message.to_string(),
+            token_type: token.token_type,
+        });
+    }
+
+    fn make_constant(&mut self, value: Value, token: Token) -> usize {
+        let constant = add_constant(&mut self.chunk, value);
+        if constant > u8::MAX as usize {
+            self.error(token, "Too many constants in one chunk.");
+            return 0;
+        }
+        constant
+    }
+
+    fn declaration(&mut self, declaration: &Declaration) {
+        match declaration {
+            Declaration::Var { name, initializer, semicolon, .. } => {
+                let global = self.identifier_constant(name);
+                match initializer {
+                    Some(initializer) => self.expression(initializer),
+                    None => self.emit(OP_NIL, name.token),
+                }
+                self.emit(OP_DEFINE_GLOBAL, *semicolon);
+                self.emit(global, *semicolon);
+            }
+            Declaration::Statement(statement) => self.statement(statement),
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Print { keyword, value, .. } => {
+                self.expression(value);
+                self.emit(OP_PRINT, *keyword);
+            }
+            Stmt::Expression { expression, semicolon, .. } => {
+                self.expression(expression);
+                self.emit(OP_POP, *semicolon);
+            }
+        }
+    }
+
+    fn identifier_constant(&mut self, name: &Identifier) -> usize {
+        self.make_constant(string_val(&name.name), name.token)
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        if let Some((value, token)) = constant(expr) {
+            self.emit_value(value, token);
+            return;
+        }
+
+        match expr {
+            Expr::Literal { value, token } => self.emit_value(value.clone(), *token),
+            Expr::Interpolation { parts, tail } => self.interpolation(parts, tail),
+            Expr::Variable(name) => {
+                let arg = self.identifier_constant(name);
+                self.emit(OP_GET_GLOBAL, name.token);
+                self.emit(arg, name.token);
+            }
+            Expr::Assign { name, value } => {
+                let arg = self.identifier_constant(name);
+                self.expression(value);
+                self.emit(OP_SET_GLOBAL, name.token);
+                self.emit(arg, name.token);
+            }
+            Expr::Unary { operator, operand } => {
+                self.expression(operand);
+                self.emit(unary_opcode(operator.token_type), *operator);
+            }
+            Expr::Binary { left, operator, right } => {
+                self.expression(left);
+                self.expression(right);
+                self.emit(binary_opcode(operator.token_type), *operator);
+            }
+            Expr::Grouping { expression, .. } => self.expression(expression),
+        }
+    }
+
+    /// Emits the instruction loading `value`: a dedicated opcode for `nil`
+    /// and the booleans, `OP_CONSTANT` for anything else.
+    fn emit_value(&mut self, value: Value, token: Token) {
+        match value.value_type() {
+            ValueType::Nil => self.emit(OP_NIL, token),
+            ValueType::Bool => self.emit(if as_bool(&value) == Some(true) { OP_TRUE } else { OP_FALSE }, token),
+            _ => {
+                let constant = self.make_constant(value, token);
+                self.emit(OP_CONSTANT, token);
+                self.emit(constant, token);
+            }
+        }
+    }
+
+    /// Emits `"a " + str(x) + " b " + str(y) + " c"`, as
+    /// `Compiler::interpolation` does.
+    fn interpolation(&mut self, parts: &[(StringSegment, Expr)], tail: &StringSegment) {
+        for (idx, (segment, expression)) in parts.iter().enumerate() {
+            self.emit_value(string_val(&segment.text), segment.token);
+            if idx > 0 { self.emit(OP_ADD, segment.token); }
+            self.expression(expression);
+            self.emit(OP_TO_STRING, segment.token);
+            self.emit(OP_ADD, segment.token);
+        }
+        self.emit_value(string_val(&tail.text), tail.token);
+        self.emit(OP_ADD, tail.token);
+    }
+}
+
+/// The value of `expr` if it folds to a constant, with the token its
+/// instruction is attributed to.
+fn constant(expr: &Expr) -> Option<(Value, Token)> {
+    match expr {
+        Expr::Literal { value, token } => Some((value.clone(), *token)),
+        Expr::Grouping { expression, .. } => constant(expression),
+        Expr::Unary { operator, operand } => {
+            let (operand, _) = constant(operand)?;
+            fold_unary(unary_opcode(operator.token_type), &operand).map(|value| (value, *operator))
+        }
+        Expr::Binary { left, operator, right } => {
+            let (left, _) = constant(left)?;
+            let (right, _) = constant(right)?;
+            fold_binary(binary_opcode(operator.token_type), &left, &right).map(|value| (value, *operator))
+        }
+        _ => None,
+    }
+}
+
+fn unary_opcode(token_type: TokenType) -> usize {
+    match token_type {
+        TokenType::Minus => OP_NEGATE,
+        TokenType::Bang => OP_NOT,
+        _ => panic!("Unreachable unary operator."),
+    }
+}
+
+fn binary_opcode(token_type: TokenType) -> usize {
+    match token_type {
+        TokenType::BangEqual => OP_NOT_EQUAL,
+        TokenType::EqualEqual => OP_EQUAL,
+        TokenType::Greater => OP_GREATER,
+        TokenType::GreaterEqual => OP_GREATER_EQUAL,
+        TokenType::Less => OP_LESS,
+        TokenType::LessEqual => OP_LESS_EQUAL,
+        TokenType::Plus => OP_ADD,
+        TokenType::Minus => OP_SUBTRACT,
+        TokenType::Star => OP_MULTIPLY,
+        TokenType::Slash => OP_DIVIDE,
+        _ => panic!("Unreachable binary operator."),
+    }
+}
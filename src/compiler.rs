@@ -1,33 +1,57 @@
-use crate::chunk::{add_constant, Chunk, OP_ADD, OP_CONSTANT, OP_DIVIDE, OP_EQUAL, OP_FALSE, OP_GREATER, OP_LESS, OP_MULTIPLY, OP_NEGATE, OP_NIL, OP_NOT, OP_RETURN, OP_SUBTRACT, OP_TRUE, write_chunk};
-use crate::scanner::{Scanner, Token, TokenType};
-use crate::value::{number_val, Value};
+use std::cmp::Ordering;
+use crate::chunk::{add_constant, Chunk, OP_ADD, OP_CONSTANT, OP_DEFINE_GLOBAL, OP_DIVIDE, OP_EQUAL, OP_FALSE, OP_GET_GLOBAL, OP_GREATER, OP_LESS, OP_MULTIPLY, OP_NEGATE, OP_NIL, OP_NOT, OP_POP, OP_PRINT, OP_RETURN, OP_SET_GLOBAL, OP_SUBTRACT, OP_TO_STRING, OP_TRUE, OP_NOT_EQUAL, OP_GREATER_EQUAL, OP_LESS_EQUAL, write_chunk};
+use crate::peephole::optimize;
+use crate::error::CompileError;
+use crate::scanner::{Edition, Scanner, Token, TokenType};
+use crate::value::{as_bool, as_number, as_string, bool_val, is_falsy, number_val, string_val, Value, ValueType, values_equal, NIL_VAL};
 use crate::parser::{Parser, PREC_ASSIGNMENT, PREC_UNARY};
 
 pub struct Compiler {
     parser: Parser,
     scanner: Scanner,
     pub compiling_chunk: Chunk,
+    pub errors: Vec<CompileError>,
+    /// Where the code for the left operand of the infix rule being
+    /// compiled starts, so `binary` can fold constant operands.
+    left_operand: Mark,
+}
+
+/// A point in the chunk being compiled: the length of its code and of its
+/// constant pool.
+#[derive(Clone, Copy, Default)]
+struct Mark {
+    code: usize,
+    constants: usize,
 }
 
 impl Compiler {
-    pub fn new(source: String, compiling_chunk: Chunk) -> Compiler {
+    pub fn new(source: String, compiling_chunk: Chunk, edition: Edition) -> Compiler {
         Compiler {
             parser: Parser::new(),
-            scanner: Scanner::new(source),
+            scanner: Scanner::with_edition(source, edition),
             compiling_chunk,
+            errors: Vec::new(),
+            left_operand: Mark::default(),
         }
     }
 
     pub fn compile(&mut self) -> bool {
         self.advance();
-        self.expression();
-        self.consume(TokenType::EOF, "Expect end of expression.".to_string());
+        while !self.match_token(TokenType::EOF) {
+            self.declaration();
+        }
         self.end_compiler();
         !self.parser.had_error
     }
 
     fn emit_byte(&mut self, byte: usize) {
-        write_chunk(&mut self.compiling_chunk, byte, self.parser.previous.line)
+        self.emit_byte_at(byte, self.parser.previous);
+    }
+
+    /// Emits `byte` with `token`'s location, for operators whose bytes are
+    /// written after their operands have been parsed.
+    fn emit_byte_at(&mut self, byte: usize, token: Token) {
+        write_chunk(&mut self.compiling_chunk, byte, token.line, token.column, token.span)
     }
 
     fn emit_bytes(&mut self, byte1: usize, byte2: usize) {
@@ -35,13 +59,13 @@ impl Compiler {
         self.emit_byte(byte2);
     }
 
-    fn advance (&mut self) {
+    fn advance(&mut self) {
         self.parser.previous = self.parser.current;
 
         loop {
             self.parser.current = self.scanner.scan_token();
             if self.parser.current.token_type != TokenType::Error { break; }
-            self.error_at_current(self.scanner.get_token_text(self.parser.current));
+            self.error_at_current(self.parser.current.message.to_string());
         }
     }
 
@@ -58,40 +82,114 @@ impl Compiler {
             return;
         }
         self.parser.panic_mode = true;
-        print!("[line {}] Error", token.line);
+        self.errors.push(CompileError {
+            line: token.line,
+            column: token.column,
+            span: token.span,
+            lexeme: self.scanner.get_token_text(token),
+            message,
+            token_type: token.token_type,
+        });
+        self.parser.had_error = true;
+    }
+
+    fn expression(&mut self) {
+        self.parse_precedence(PREC_ASSIGNMENT);
+    }
 
-        if token.token_type == TokenType::EOF {
-            print!(" at end.");
+    fn declaration(&mut self) {
+        if self.match_token(TokenType::Var) {
+            self.var_declaration();
         }
-        else if token.token_type == TokenType::Error {
-            // Nothing.
+        else {
+            self.statement();
+        }
+
+        if self.parser.panic_mode { self.synchronize(); }
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.".to_string());
+
+        if self.match_token(TokenType::Equal) {
+            self.expression();
         }
         else {
-            print!(" at '{}, {}'", token.length, token.start);
+            self.emit_byte(OP_NIL);
         }
+        self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.".to_string());
 
-        println!(": {}", message);
+        self.emit_bytes(OP_DEFINE_GLOBAL, global);
+    }
 
-        self.parser.had_error = true;
+    fn parse_variable(&mut self, message: String) -> usize {
+        self.consume(TokenType::Identifier, message);
+        self.identifier_constant(self.parser.previous)
     }
 
-    fn expression(&mut self) {
-        self.parse_precedence(PREC_ASSIGNMENT);
+    fn identifier_constant(&mut self, name: Token) -> usize {
+        let name = self.scanner.get_token_text(name);
+        self.make_constant(string_val(&name))
+    }
+
+    fn statement(&mut self) {
+        if self.match_token(TokenType::Print) {
+            self.print_statement();
+        }
+        else {
+            self.expression_statement();
+        }
+    }
+
+    fn print_statement(&mut self) {
+        let keyword = self.parser.previous;
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string());
+        self.emit_byte_at(OP_PRINT, keyword);
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.".to_string());
+        self.emit_byte(OP_POP);
+    }
+
+    /// Skips tokens until a likely statement boundary after a syntax error,
+    /// so the errors reported after it aren't just fallout from the first.
+    fn synchronize(&mut self) {
+        self.parser.panic_mode = false;
+
+        while self.parser.current.token_type != TokenType::EOF {
+            if self.parser.previous.token_type == TokenType::Semicolon { return; }
+            match self.parser.current.token_type {
+                TokenType::Class | TokenType::Fun | TokenType::Var | TokenType::For |
+                TokenType::If | TokenType::While | TokenType::Print | TokenType::Return => return,
+                _ => {},
+            }
+            self.advance();
+        }
     }
 
     fn parse_precedence(&mut self, precedence: i32) {
         self.advance();
         let prefix_rule = self.parser.get_rule(self.parser.previous.token_type).prefix;
-        if prefix_rule as usize == Compiler::nil as usize {
+        if prefix_rule as *const () == Compiler::nil as *const () {
             self.error("Expect expression.".to_string());
             return;
         }
-        prefix_rule(self);
+        let can_assign = precedence <= PREC_ASSIGNMENT;
+        let start = self.mark();
+        prefix_rule(self, can_assign);
 
         while precedence <= self.parser.get_rule(self.parser.current.token_type).precedence {
             self.advance();
             let infix_rule = self.parser.get_rule(self.parser.previous.token_type).infix;
-            infix_rule(self);
+            self.left_operand = start;
+            infix_rule(self, can_assign);
+        }
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.error("Invalid assignment target.".to_string());
         }
     }
 
@@ -103,31 +201,94 @@ impl Compiler {
         self.error_at_current(message);
     }
 
+    fn check(&self, token_type: TokenType) -> bool {
+        self.parser.current.token_type == token_type
+    }
+
+    fn match_token(&mut self, token_type: TokenType) -> bool {
+        if !self.check(token_type) { return false; }
+        self.advance();
+        true
+    }
+
     fn end_compiler(&mut self) {
         self.emit_return();
+        if !self.parser.had_error {
+            optimize(&mut self.compiling_chunk);
+        }
     }
 
-    pub fn binary(&mut self) {
-        let operator_type = self.parser.previous.token_type;
-        let rule = self.parser.get_rule(operator_type);
+    pub fn binary(&mut self, _can_assign: bool) {
+        let operator = self.parser.previous;
+        let left_start = self.left_operand;
+        let right_start = self.mark();
+        let rule = self.parser.get_rule(operator.token_type);
         self.parse_precedence(rule.precedence + 1);
 
-        match operator_type {
-            TokenType::BangEqual => { self.emit_bytes(OP_EQUAL, OP_NOT); },
-            TokenType::EqualEqual => { self.emit_byte(OP_EQUAL); },
-            TokenType::Greater => { self.emit_byte(OP_GREATER); },
-            TokenType::GreaterEqual => { self.emit_bytes(OP_LESS, OP_NOT); },
-            TokenType::Less => { self.emit_byte(OP_LESS); },
-            TokenType::LessEqual => { self.emit_bytes(OP_GREATER, OP_NOT); },
-            TokenType::Plus => { self.emit_byte(OP_ADD); },
-            TokenType::Minus => { self.emit_byte(OP_SUBTRACT); },
-            TokenType::Star => { self.emit_byte(OP_MULTIPLY); },
-            TokenType::Slash =>  { self.emit_byte(OP_DIVIDE); },
+        let opcode = match operator.token_type {
+            TokenType::BangEqual => OP_NOT_EQUAL,
+            TokenType::EqualEqual => OP_EQUAL,
+            TokenType::Greater => OP_GREATER,
+            TokenType::GreaterEqual => OP_GREATER_EQUAL,
+            TokenType::Less => OP_LESS,
+            TokenType::LessEqual => OP_LESS_EQUAL,
+            TokenType::Plus => OP_ADD,
+            TokenType::Minus => OP_SUBTRACT,
+            TokenType::Star => OP_MULTIPLY,
+            TokenType::Slash => OP_DIVIDE,
             _ => { panic!("Unreachable binary operator.")},
+        };
+
+        let operands = (self.constant_between(left_start, right_start), self.constant_between(right_start, self.mark()));
+        if let (Some(a), Some(b)) = operands {
+            if let Some(value) = fold_binary(opcode, &a, &b) {
+                self.replace_with_constant(left_start, value, operator);
+                return;
+            }
+        }
+
+        self.emit_byte_at(opcode, operator);
+    }
+
+    fn mark(&self) -> Mark {
+        Mark { code: self.compiling_chunk.code.len(), constants: self.compiling_chunk.constants.len() }
+    }
+
+    /// The value loaded by the code from `start` to `end`, if that code is a
+    /// single instruction pushing a constant.
+    fn constant_between(&self, start: Mark, end: Mark) -> Option<Value> {
+        let code = &self.compiling_chunk.code[start.code..end.code];
+        match code {
+            [OP_CONSTANT, index] => self.compiling_chunk.constants.get(*index).cloned(),
+            [OP_NIL] => Some(NIL_VAL),
+            [OP_TRUE] => Some(bool_val(true)),
+            [OP_FALSE] => Some(bool_val(false)),
+            _ => None,
         }
     }
 
-    pub fn grouping(&mut self) {
+    /// Replaces everything compiled since `start` with code loading `value`.
+    /// Constants added since `start` were only used by that code.
+    fn replace_with_constant(&mut self, start: Mark, value: Value, token: Token) {
+        let chunk = &mut self.compiling_chunk;
+        chunk.code.truncate(start.code);
+        chunk.lines.truncate(start.code);
+        chunk.columns.truncate(start.code);
+        chunk.spans.truncate(start.code);
+        chunk.constants.truncate(start.constants);
+
+        match value.value_type() {
+            ValueType::Nil => self.emit_byte_at(OP_NIL, token),
+            ValueType::Bool => self.emit_byte_at(if as_bool(&value) == Some(true) { OP_TRUE } else { OP_FALSE }, token),
+            _ => {
+                let constant = self.make_constant(value);
+                self.emit_byte_at(OP_CONSTANT, token);
+                self.emit_byte_at(constant, token);
+            }
+        }
+    }
+
+    pub fn grouping(&mut self, _can_assign: bool) {
         self.expression();
         self.consume(TokenType::RightParen, "Expect ')' after expression.".to_string());
     }
@@ -143,32 +304,101 @@ impl Compiler {
 
     fn make_constant(&mut self, value: Value) -> usize {
         let constant = add_constant(&mut self.compiling_chunk, value);
-        if constant > 10 {
+        if constant > u8::MAX as usize {
+            self.error("Too many constants in one chunk.".to_string());
             return 0;
         }
         constant
     }
 
-    pub fn number(&mut self) {
-        let number: f64 = self.scanner.get_token_text(self.parser.previous).parse().unwrap();
-        self.emit_constant(number_val(number));
+    pub fn number(&mut self, _can_assign: bool) {
+        let text = self.scanner.get_token_text(self.parser.previous);
+        match parse_number(&text) {
+            Some(number) if number.is_infinite() => self.error("Number literal is too large.".to_string()),
+            Some(number) => self.emit_constant(number_val(number)),
+            None => self.error("Invalid number literal.".to_string()),
+        }
+    }
+
+    pub fn string(&mut self, _can_assign: bool) {
+        let text = self.scanner.get_token_text(self.parser.previous);
+        self.string_segment(&text[1..text.len() - 1]);
+    }
+
+    /// Compiles `"a ${x} b ${y} c"` as `"a " + str(x) + " b " + str(y) +
+    /// " c"`, where `str` is `OP_TO_STRING`. Each segment token carries the
+    /// text between its opening `"` or `}` and its closing `${` or `"`.
+    pub fn interpolation(&mut self, _can_assign: bool) {
+        let mut first = true;
+        loop {
+            let segment = self.parser.previous;
+            let text = self.scanner.get_token_text(segment);
+            self.string_segment(&text[1..text.len() - 2]);
+            if !first { self.emit_byte_at(OP_ADD, segment); }
+            first = false;
+
+            self.expression();
+            self.emit_byte_at(OP_TO_STRING, segment);
+            self.emit_byte_at(OP_ADD, segment);
+
+            match self.parser.current.token_type {
+                TokenType::Interpolation => self.advance(),
+                TokenType::String => break,
+                _ => {
+                    self.error_at_current("Expect '}' after interpolated expression.".to_string());
+                    return;
+                }
+            }
+        }
+        self.advance();
+        self.string(false);
+        self.emit_byte(OP_ADD);
+    }
+
+    fn string_segment(&mut self, raw: &str) {
+        match unescape(raw) {
+            Ok(chars) => self.emit_constant(string_val(&chars)),
+            Err(message) => self.error(message),
+        }
+    }
+
+    pub fn variable(&mut self, can_assign: bool) {
+        self.named_variable(self.parser.previous, can_assign);
     }
 
-    pub fn string(&mut self) {
-        self.emit_constant(number_val(10.0));
+    fn named_variable(&mut self, name: Token, can_assign: bool) {
+        let arg = self.identifier_constant(name);
+
+        if can_assign && self.match_token(TokenType::Equal) {
+            self.expression();
+            self.emit_byte_at(OP_SET_GLOBAL, name);
+            self.emit_byte_at(arg, name);
+        }
+        else {
+            self.emit_byte_at(OP_GET_GLOBAL, name);
+            self.emit_byte_at(arg, name);
+        }
     }
 
-    pub fn unary(&mut self) {
-        let operator_type = self.parser.previous.token_type;
+    pub fn unary(&mut self, _can_assign: bool) {
+        let operator = self.parser.previous;
+        let start = self.mark();
         self.parse_precedence(PREC_UNARY);
-        match operator_type {
-            TokenType::Minus => { self.emit_byte(OP_NEGATE); }
-            TokenType::Bang => { self.emit_byte(OP_NOT); }
-            _ => {},
+        let opcode = match operator.token_type {
+            TokenType::Minus => OP_NEGATE,
+            TokenType::Bang => OP_NOT,
+            _ => return,
+        };
+
+        let folded = self.constant_between(start, self.mark())
+            .and_then(|operand| fold_unary(opcode, &operand));
+        match folded {
+            Some(value) => self.replace_with_constant(start, value, operator),
+            None => self.emit_byte_at(opcode, operator),
         }
     }
 
-    pub fn literal(&mut self) {
+    pub fn literal(&mut self, _can_assign: bool) {
         match self.parser.previous.token_type {
             TokenType::True => { self.emit_byte(OP_TRUE); },
             TokenType::False => { self.emit_byte(OP_FALSE); },
@@ -177,5 +407,91 @@ impl Compiler {
         }
     }
 
-    pub fn nil(&mut self) {}
+    pub fn nil(&mut self, _can_assign: bool) {}
+}
+
+/// Evaluates a binary opcode on constant operands the way the VM would, or
+/// returns `None` where the VM would raise an error, leaving that to happen
+/// at run time.
+pub(crate) fn fold_binary(opcode: usize, a: &Value, b: &Value) -> Option<Value> {
+    if opcode == OP_EQUAL { return Some(bool_val(values_equal(a, b))); }
+    if opcode == OP_NOT_EQUAL { return Some(bool_val(!values_equal(a, b))); }
+    if opcode == OP_ADD {
+        if let (Some(a), Some(b)) = (as_string(a), as_string(b)) {
+            return Some(string_val(&(a.to_string() + b)));
+        }
+    }
+    let (a, b) = (as_number(a)?, as_number(b)?);
+    let value = match opcode {
+        OP_ADD => number_val(a + b),
+        OP_SUBTRACT => number_val(a - b),
+        OP_MULTIPLY => number_val(a * b),
+        OP_DIVIDE => number_val(a / b),
+        OP_GREATER => bool_val(a > b),
+        OP_LESS => bool_val(a < b),
+        OP_GREATER_EQUAL => bool_val(a.partial_cmp(&b) != Some(Ordering::Less)),
+        OP_LESS_EQUAL => bool_val(a.partial_cmp(&b) != Some(Ordering::Greater)),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Like `fold_binary`, for `OP_NOT` and `OP_NEGATE`.
+pub(crate) fn fold_unary(opcode: usize, value: &Value) -> Option<Value> {
+    match opcode {
+        OP_NOT => Some(bool_val(is_falsy(value))),
+        OP_NEGATE => as_number(value).map(|number| number_val(-number)),
+        _ => None,
+    }
+}
+
+/// Converts the text of a number token to its value. Hexadecimal and binary
+/// literals too large for an `f64` come out as infinity, as decimal ones do.
+pub(crate) fn parse_number(text: &str) -> Option<f64> {
+    let digits = text.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x") | Some("0X") => 16,
+        Some("0b") | Some("0B") => 2,
+        _ => return digits.parse().ok(),
+    };
+    digits[2..].chars().try_fold(0.0, |value: f64, c| {
+        c.to_digit(radix).map(|digit| value * radix as f64 + digit as f64)
+    })
+}
+
+/// Decodes the escape sequences in the body of a string literal: `\n`,
+/// `\t`, `\"`, `\\`, `\$` and `\u{XXXX}` with one to six hex digits naming a
+/// Unicode scalar value.
+pub(crate) fn unescape(raw: &str) -> Result<String, String> {
+    let mut chars = raw.chars();
+    let mut decoded = String::with_capacity(raw.len());
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => decoded.push('\n'),
+            Some('t') => decoded.push('\t'),
+            Some('"') => decoded.push('"'),
+            Some('\\') => decoded.push('\\'),
+            Some('$') => decoded.push('$'),
+            Some('u') => {
+                let rest = chars.as_str();
+                let digits = rest.strip_prefix('{')
+                    .and_then(|rest| rest.find('}').map(|end| &rest[..end]))
+                    .filter(|digits| (1..=6).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_hexdigit()))
+                    .ok_or_else(|| "Invalid unicode escape; expected '\\u{XXXX}'.".to_string())?;
+                let scalar = u32::from_str_radix(digits, 16).ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("Invalid unicode escape '\\u{{{}}}'.", digits))?;
+                decoded.push(scalar);
+                chars = rest[digits.len() + 2..].chars();
+            }
+            Some(other) => return Err(format!("Invalid escape sequence '\\{}'.", other)),
+            None => return Err("Invalid escape sequence '\\'.".to_string()),
+        }
+    }
+    Ok(decoded)
 }
@@ -1,82 +1,145 @@
-use crate::chunk::{Chunk, OP_RETURN, OP_CONSTANT, OP_NEGATE, OP_ADD, OP_SUBTRACT, OP_MULTIPLY, OP_DIVIDE, OP_NIL, OP_TRUE, OP_FALSE, OP_NOT, OP_EQUAL, OP_GREATER, OP_LESS};
-use crate::value::print_value;
-// use crate::value::print_value;
+use crate::chunk::{Chunk, OP_RETURN, OP_CONSTANT, OP_NEGATE, OP_ADD, OP_SUBTRACT, OP_MULTIPLY, OP_DIVIDE, OP_NIL, OP_TRUE, OP_FALSE, OP_NOT, OP_EQUAL, OP_GREATER, OP_LESS, OP_GET_GLOBAL, OP_TO_STRING, OP_PRINT, OP_POP, OP_DEFINE_GLOBAL, OP_SET_GLOBAL, OP_NOT_EQUAL, OP_GREATER_EQUAL, OP_LESS_EQUAL, OP_GET_GLOBAL_ADD_CONSTANT};
+use std::io::Write;
 
-// pub fn disassemble_chunk(chunk: &Chunk, name: String) {
-//     println!("== {} == ", name);
-//
-//     let mut offset = 0;
-//     while offset < chunk.code.len() {
-//         offset = disassemble_instruction(chunk, offset);
-//     }
-// }
+use crate::value::{as_string, Value};
 
-fn constant_instruction(name: String, chunk: &Chunk, offset: usize) -> usize {
+/// Writes a listing of every instruction in `chunk` under a `== name ==`
+/// header.
+pub fn disassemble_chunk(out: &mut dyn Write, chunk: &Chunk, name: &str) {
+    let _ = writeln!(out, "== {} ==", name);
+
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = disassemble_instruction(out, chunk, offset);
+    }
+}
+
+/// How a constant appears in a listing: strings double-quoted with their
+/// special characters escaped as in Lox source, so `"5"` can't be mistaken
+/// for the number, and anything else single-quoted.
+pub fn constant_literal(value: &Value) -> String {
+    let chars = match as_string(value) {
+        Some(chars) => chars,
+        None => return format!("'{}'", value),
+    };
+    let mut literal = String::with_capacity(chars.len() + 2);
+    literal.push('"');
+    for c in chars.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn constant_instruction(out: &mut dyn Write, name: String, chunk: &Chunk, offset: usize) -> usize {
     let constant_idx = chunk.code[offset + 1];
-    print!("{:16} {:4} '", name, constant_idx);
-    print_value(chunk.constants[constant_idx]);
-    print!("'\n");
-    return offset + 2;
+    let _ = writeln!(out, "{:16} {:4} {}", name, constant_idx, constant_literal(&chunk.constants[constant_idx]));
+    offset + 2
+}
+
+fn two_constant_instruction(out: &mut dyn Write, name: String, chunk: &Chunk, offset: usize) -> usize {
+    let first = chunk.code[offset + 1];
+    let second = chunk.code[offset + 2];
+    let (first_literal, second_literal) = (constant_literal(&chunk.constants[first]), constant_literal(&chunk.constants[second]));
+    let _ = writeln!(out, "{:16} {:4} {} {:4} {}", name, first, first_literal, second, second_literal);
+    offset + 3
 }
 
-fn simple_instruction(name: String, offset: usize) -> usize {
-    println!("{}", name);
-    return offset + 1;
+fn simple_instruction(out: &mut dyn Write, name: String, offset: usize) -> usize {
+    let _ = writeln!(out, "{}", name);
+    offset + 1
 }
 
-pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize {
-    print!("{:04} ", offset);
+pub fn disassemble_instruction(out: &mut dyn Write, chunk: &Chunk, offset: usize) -> usize {
+    let _ = write!(out, "{:04} ", offset);
 
     if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
-        print!("   | ");
+        let _ = write!(out, "   | ");
     }
     else {
-        print!("{:4} ", chunk.lines[offset]);
+        let _ = write!(out, "{:4} ", chunk.lines[offset]);
     }
 
     let instruction = chunk.code[offset];
     if instruction == OP_RETURN {
-        return simple_instruction("OP_RETURN".to_string(), offset);
+        return simple_instruction(out, "OP_RETURN".to_string(), offset);
     }
     else if instruction == OP_CONSTANT {
-        return constant_instruction("OP_CONSTANT".to_string(), chunk, offset);
+        return constant_instruction(out, "OP_CONSTANT".to_string(), chunk, offset);
+    }
+    else if instruction == OP_GET_GLOBAL {
+        return constant_instruction(out, String::from("OP_GET_GLOBAL"), chunk, offset);
+    }
+    else if instruction == OP_DEFINE_GLOBAL {
+        return constant_instruction(out, String::from("OP_DEFINE_GLOBAL"), chunk, offset);
+    }
+    else if instruction == OP_SET_GLOBAL {
+        return constant_instruction(out, String::from("OP_SET_GLOBAL"), chunk, offset);
+    }
+    else if instruction == OP_PRINT {
+        return simple_instruction(out, String::from("OP_PRINT"), offset);
+    }
+    else if instruction == OP_POP {
+        return simple_instruction(out, String::from("OP_POP"), offset);
+    }
+    else if instruction == OP_GET_GLOBAL_ADD_CONSTANT {
+        return two_constant_instruction(out, String::from("OP_GET_GLOBAL_ADD_CONSTANT"), chunk, offset);
     }
     else if instruction == OP_NIL {
-        return simple_instruction(String::from("OP_NIL"), offset);
+        return simple_instruction(out, String::from("OP_NIL"), offset);
     }
     else if instruction == OP_TRUE {
-        return simple_instruction(String::from("OP_TRUE"), offset);
+        return simple_instruction(out, String::from("OP_TRUE"), offset);
     }
     else if instruction == OP_FALSE {
-        return simple_instruction(String::from("OP_FALSE"), offset);
+        return simple_instruction(out, String::from("OP_FALSE"), offset);
     }
     else if instruction == OP_EQUAL {
-        return simple_instruction(String::from("OP_EQUAL"), offset);
+        return simple_instruction(out, String::from("OP_EQUAL"), offset);
+    }
+    else if instruction == OP_NOT_EQUAL {
+        return simple_instruction(out, String::from("OP_NOT_EQUAL"), offset);
+    }
+    else if instruction == OP_GREATER_EQUAL {
+        return simple_instruction(out, String::from("OP_GREATER_EQUAL"), offset);
+    }
+    else if instruction == OP_LESS_EQUAL {
+        return simple_instruction(out, String::from("OP_LESS_EQUAL"), offset);
     }
     else if instruction == OP_GREATER {
-        return simple_instruction(String::from("OP_GREATER"), offset);
+        return simple_instruction(out, String::from("OP_GREATER"), offset);
     }
     else if instruction == OP_LESS {
-        return simple_instruction(String::from("OP_LESS"), offset);
+        return simple_instruction(out, String::from("OP_LESS"), offset);
     }
     else if instruction == OP_NOT {
-        return simple_instruction(String::from("OP_NOT"), offset);
+        return simple_instruction(out, String::from("OP_NOT"), offset);
     }
     else if instruction == OP_ADD {
-        return simple_instruction(String::from("OP_ADD"), offset);
+        return simple_instruction(out, String::from("OP_ADD"), offset);
     }
     else if instruction == OP_SUBTRACT {
-        return simple_instruction(String::from("OP_SUBTRACT"), offset);
+        return simple_instruction(out, String::from("OP_SUBTRACT"), offset);
     }
     else if instruction == OP_MULTIPLY {
-        return simple_instruction(String::from("OP_MULTIPLY"), offset);
+        return simple_instruction(out, String::from("OP_MULTIPLY"), offset);
     }
     else if instruction == OP_DIVIDE {
-        return simple_instruction(String::from("OP_DIVIDE"), offset);
+        return simple_instruction(out, String::from("OP_DIVIDE"), offset);
+    }
+    else if instruction == OP_TO_STRING {
+        return simple_instruction(out, String::from("OP_TO_STRING"), offset);
     }
     else if instruction == OP_NEGATE {
-        return simple_instruction("OP_NEGATE".to_string(), offset);
+        return simple_instruction(out, "OP_NEGATE".to_string(), offset);
     }
-    println!("Unknown opcode {:?}", instruction);
-    return offset + 1;
+    let _ = writeln!(out, "Unknown opcode {:?}", instruction);
+    offset + 1
 }
\ No newline at end of file
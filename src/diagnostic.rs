@@ -0,0 +1,125 @@
+use crate::error::{BytecodeError, CompileError, LoxError, RuntimeError};
+use crate::scanner::{Span, TokenType};
+
+/// How the VM reports errors on its diagnostics sink.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticStyle {
+    /// One `[line N] Error ...` line per error, as clox prints them.
+    Short,
+    /// The offending source line with the span underlined, without color.
+    Plain,
+    /// Like `Plain`, with ANSI colors for terminals.
+    Color,
+}
+
+/// A renderable error: a message anchored to a source span, plus optional
+/// notes and a hint for fixing common mistakes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Diagnostic {
+        Diagnostic {
+            message: error.message.clone(),
+            span: error.span,
+            notes: Vec::new(),
+            help: help_for(error),
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Diagnostic {
+        Diagnostic {
+            message: error.message.clone(),
+            span: error.stack_trace.first().map(|frame| frame.span).unwrap_or_default(),
+            notes: error.stack_trace.iter().map(|frame| frame.to_string()).collect(),
+            help: None,
+        }
+    }
+}
+
+impl From<&BytecodeError> for Diagnostic {
+    fn from(error: &BytecodeError) -> Diagnostic {
+        Diagnostic { message: error.message.clone(), span: Span::default(), notes: Vec::new(), help: None }
+    }
+}
+
+fn help_for(error: &CompileError) -> Option<String> {
+    if error.message.starts_with("Expect ';'") {
+        return Some("add a ';' to end the previous statement".to_string());
+    }
+    if error.token_type == TokenType::Equal {
+        return Some("use '==' to compare values; '=' is assignment".to_string());
+    }
+    if error.message == "Unterminated string." {
+        return Some("close the string with '\"'".to_string());
+    }
+    None
+}
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+
+/// Renders `error` the way `style` asks, ending with a newline.
+pub fn render_error(source: &str, error: &LoxError, style: DiagnosticStyle) -> String {
+    if style == DiagnosticStyle::Short {
+        return format!("{}\n", error);
+    }
+
+    let diagnostics: Vec<Diagnostic> = match error {
+        LoxError::CompileError(errors) => errors.iter().map(Diagnostic::from).collect(),
+        LoxError::RuntimeError(error) => vec![Diagnostic::from(error)],
+        LoxError::BytecodeError(error) => vec![Diagnostic::from(error)],
+    };
+    diagnostics.iter()
+        .map(|diagnostic| render(source, diagnostic, style == DiagnosticStyle::Color))
+        .collect()
+}
+
+/// Renders `diagnostic` with the source line it points at, e.g.
+///
+/// ```text
+/// error: Expect expression.
+///  --> 1:5
+///   |
+/// 1 | 1 * ;
+///   |     ^
+///   = help: ...
+/// ```
+pub fn render(source: &str, diagnostic: &Diagnostic, color: bool) -> String {
+    let paint = |code: &'static str| if color { code } else { "" };
+    let (red, blue, bold, reset) = (paint(RED), paint(BLUE), paint(BOLD), paint(RESET));
+
+    let start = diagnostic.span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |idx| idx + 1);
+    let line_end = source[start..].find('\n').map_or(source.len(), |idx| start + idx);
+    let line_number = source[..start].matches('\n').count() + 1;
+    let text = &source[line_start..line_end];
+
+    let column = source[line_start..start].chars().count();
+    let end = diagnostic.span.end.clamp(start, line_end);
+    let width = source[start..end].chars().count().max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    let mut out = String::new();
+    out.push_str(&format!("{}error{}: {}{}{}\n", red, reset, bold, diagnostic.message, reset));
+    out.push_str(&format!("{}{}-->{} {}:{}\n", gutter, blue, reset, line_number, column + 1));
+    out.push_str(&format!("{} {}|{}\n", gutter, blue, reset));
+    out.push_str(&format!("{}{} |{} {}\n", blue, line_number, reset, text));
+    out.push_str(&format!("{} {}|{} {}{}{}{}\n", gutter, blue, reset, " ".repeat(column), red, "^".repeat(width), reset));
+    for note in &diagnostic.notes {
+        out.push_str(&format!("{} {}={} note: {}\n", gutter, blue, reset, note));
+    }
+    if let Some(help) = &diagnostic.help {
+        out.push_str(&format!("{} {}={} help: {}\n", gutter, blue, reset, help));
+    }
+    out
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use crate::scanner::{Span, TokenType};
+
+/// A syntax error reported by the compiler at the token of type `token_type`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub line: i32,
+    pub column: usize,
+    pub span: Span,
+    pub lexeme: String,
+    pub message: String,
+    pub token_type: TokenType,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] Error", self.line)?;
+        match self.token_type {
+            TokenType::EOF => write!(f, " at end")?,
+            TokenType::Error => {},
+            _ => write!(f, " at '{}'", self.lexeme)?,
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: String,
+    pub line: i32,
+    pub column: usize,
+    pub span: Span,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] in {}", self.line, self.function)
+    }
+}
+
+/// An error raised while executing bytecode, with the innermost frame first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub stack_trace: Vec<StackFrame>,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for frame in &self.stack_trace {
+            write!(f, "\n{}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// A `.loxc` file that can't be written or loaded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BytecodeError {
+    pub message: String,
+}
+
+impl fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for BytecodeError {}
+
+/// A line of an assembly listing that doesn't describe an instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[line {}] {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoxError {
+    CompileError(Vec<CompileError>),
+    RuntimeError(RuntimeError),
+    /// A chunk rejected by the verifier before it ran.
+    BytecodeError(BytecodeError),
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoxError::CompileError(errors) => {
+                let lines: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            LoxError::RuntimeError(error) => write!(f, "{}", error),
+            LoxError::BytecodeError(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoxError::CompileError(errors) => errors.first().map(|error| error as &(dyn std::error::Error + 'static)),
+            LoxError::RuntimeError(error) => Some(error),
+            LoxError::BytecodeError(error) => Some(error),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Reformats Lox source from the scanner's lossless token stream, so
+//! comments survive untouched. Line breaks are kept as written, with runs
+//! of blank lines collapsed to one; each line is re-indented by brace depth,
+//! plus one level when it continues an unfinished statement; and the spacing
+//! between tokens on a line is normalized.
+
+use crate::error::CompileError;
+use crate::scanner::{Edition, Scanner, Token, TokenType};
+
+const INDENT: &str = "  ";
+
+/// Returns `source` formatted, or the scanner's errors if it has any.
+pub fn format_source(source: &str, edition: Edition) -> Result<String, Vec<CompileError>> {
+    let mut scanner = Scanner::with_edition(source.to_string(), edition);
+    scanner.set_lossless(true);
+
+    let mut errors = Vec::new();
+    let mut lines: Vec<Vec<Item>> = vec![Vec::new()];
+    let mut previous: Option<Token> = None;
+
+    for token in scanner {
+        match token.token_type {
+            TokenType::EOF => break,
+            TokenType::Error => errors.push(CompileError {
+                line: token.line,
+                column: token.column,
+                span: token.span,
+                lexeme: token.text(source).to_string(),
+                message: token.message.to_string(),
+                token_type: token.token_type,
+            }),
+            TokenType::Whitespace => {
+                for _ in token.text(source).matches('\n') { lines.push(Vec::new()); }
+            }
+            _ => {
+                let unary = match token.token_type {
+                    TokenType::Bang => true,
+                    TokenType::Minus => !previous.is_some_and(|previous| ends_operand(previous.token_type)),
+                    _ => false,
+                };
+                if token.token_type != TokenType::Comment { previous = Some(token); }
+                lines.last_mut().unwrap().push(Item { token, unary });
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut formatted = String::with_capacity(source.len());
+    let mut depth: usize = 0;
+    let mut statement_open = false;
+    let mut blank = false;
+
+    for line in lines {
+        if line.is_empty() {
+            blank = !formatted.is_empty();
+            continue;
+        }
+        if blank { formatted.push('\n'); }
+        blank = false;
+
+        let first = line[0].token.token_type;
+        let closes = line.iter().take_while(|item| item.token.token_type == TokenType::RightBrace).count();
+        let continues = statement_open && !matches!(first, TokenType::LeftBrace | TokenType::RightBrace);
+        let level = depth.saturating_sub(closes) + continues as usize;
+        formatted.push_str(&INDENT.repeat(level));
+
+        for (idx, item) in line.iter().enumerate() {
+            if idx > 0 && space_between(&line[idx - 1], item, source) { formatted.push(' '); }
+            let text = item.token.text(source);
+            formatted.push_str(if text.starts_with("//") { text.trim_end() } else { text });
+
+            match item.token.token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        formatted.push('\n');
+
+        if let Some(last) = line.iter().rev().find(|item| item.token.token_type != TokenType::Comment) {
+            statement_open = !matches!(last.token.token_type, TokenType::Semicolon | TokenType::LeftBrace | TokenType::RightBrace);
+        }
+    }
+    Ok(formatted)
+}
+
+/// A token on a line, and whether it is a prefix operator.
+struct Item {
+    token: Token,
+    unary: bool,
+}
+
+/// Whether `token_type` can end an operand, making a following `-` binary.
+fn ends_operand(token_type: TokenType) -> bool {
+    matches!(token_type,
+        TokenType::Number | TokenType::String | TokenType::Identifier | TokenType::RightParen |
+        TokenType::True | TokenType::False | TokenType::Nil | TokenType::This | TokenType::Super)
+}
+
+fn space_between(left: &Item, right: &Item, source: &str) -> bool {
+    let (left_type, right_type) = (left.token.token_type, right.token.token_type);
+    if left_type == TokenType::Comment || right_type == TokenType::Comment { return true; }
+
+    // `"a ${` and `} b"` hug the expression between them.
+    if left_type == TokenType::Interpolation { return false; }
+    if matches!(right_type, TokenType::String | TokenType::Interpolation) && right.token.text(source).starts_with('}') {
+        return false;
+    }
+
+    match (left_type, right_type) {
+        (_, TokenType::RightParen | TokenType::Comma | TokenType::Semicolon | TokenType::Dot) => false,
+        (TokenType::LeftParen | TokenType::Dot, _) | (TokenType::LeftBrace, TokenType::RightBrace) => false,
+        _ if left.unary => false,
+        (TokenType::Identifier | TokenType::RightParen | TokenType::This | TokenType::Super, TokenType::LeftParen) => false,
+        _ => true,
+    }
+}
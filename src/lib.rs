@@ -0,0 +1,27 @@
+pub mod value;
+pub mod object;
+pub mod chunk;
+pub mod peephole;
+pub mod bytecode;
+pub mod verifier;
+pub mod debug;
+pub mod assembler;
+pub mod diagnostic;
+pub mod error;
+pub mod vm;
+pub mod output;
+pub mod scanner;
+pub mod ast;
+pub mod ast_parser;
+pub mod codegen;
+pub mod formatter;
+mod compiler;
+mod parser;
+
+pub use crate::value::Value;
+pub use crate::diagnostic::DiagnosticStyle;
+pub use crate::error::{AssemblyError, BytecodeError, CompileError, LoxError, RuntimeError};
+pub use crate::scanner::Edition;
+pub use crate::ast::FrontEnd;
+pub use crate::output::{run_captured, CapturedRun, SharedBuffer};
+pub use crate::vm::Vm;
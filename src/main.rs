@@ -1,50 +1,245 @@
-use std::{env, io};
-use std::io::Write;
+use std::{env, io, process};
+use std::io::{IsTerminal, Write};
 use std::fs;
+use std::path::Path;
 
-// use crate::chunk::{OP_DIVIDE, OP_NEGATE, OP_RETURN, write_chunk};
-// use crate::debug::disassemble_chunk;
-use crate::vm::interpret;
-use crate::vm::InterpretResult;
+use rlox::ast_parser::parse;
+use rlox::bytecode::{deserialize, serialize};
+use rlox::debug::disassemble_chunk;
+use rlox::formatter::format_source;
+use rlox::scanner::{Scanner, TokenType};
+use rlox::{DiagnosticStyle, Edition, FrontEnd, LoxError, Vm};
 
-mod value;
-mod chunk;
-mod debug;
-mod vm;
-mod compiler;
-mod scanner;
-mod parser;
+const USAGE: &str = "\
+Usage: rlox [options] [--tokens | --disassemble] [path]
+       rlox [options] compile <path> [-o <output>]
+       rlox [options] fmt [--check] <path>...
+Options: --diagnostics=short|plain|color --edition=classic|extended
+         --front-end=single-pass|ast";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let arg_len = args.len();
-    if arg_len == 1 {
-        repl();
+    let mut style = if io::stderr().is_terminal() { DiagnosticStyle::Color } else { DiagnosticStyle::Short };
+    let mut edition = Edition::Classic;
+    let mut front_end = FrontEnd::SinglePass;
+    let mut tokens = false;
+    let mut disassemble = false;
+    let mut check = false;
+    let mut output = None;
+    let mut paths = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--diagnostics=") {
+            style = match value {
+                "short" => DiagnosticStyle::Short,
+                "plain" => DiagnosticStyle::Plain,
+                "color" => DiagnosticStyle::Color,
+                _ => usage(),
+            };
+        }
+        else if let Some(value) = arg.strip_prefix("--edition=") {
+            edition = match value {
+                "classic" => Edition::Classic,
+                "extended" => Edition::Extended,
+                _ => usage(),
+            };
+        }
+        else if let Some(value) = arg.strip_prefix("--front-end=") {
+            front_end = match value {
+                "single-pass" => FrontEnd::SinglePass,
+                "ast" => FrontEnd::Ast,
+                _ => usage(),
+            };
+        }
+        else if arg == "--tokens" {
+            tokens = true;
+        }
+        else if arg == "--disassemble" {
+            disassemble = true;
+        }
+        else if arg == "--check" {
+            check = true;
+        }
+        else if arg == "-o" {
+            output = Some(args.next().unwrap_or_else(|| usage()));
+        }
+        else if arg.starts_with('-') {
+            usage();
+        }
+        else {
+            paths.push(arg);
+        }
     }
-    if arg_len == 2 {
-        run_file(&args[1]);
+
+    let settings = Settings { style, edition, front_end };
+    match paths.as_slice() {
+        [command, files @ ..] if command == "fmt" && !files.is_empty() && !tokens && !disassemble && output.is_none() => {
+            format_files(files, check, edition)
+        }
+        _ if check => usage(),
+        [command, path] if command == "compile" && !tokens && !disassemble => compile_file(path, output, settings),
+        _ if output.is_some() => usage(),
+        _ if tokens && disassemble => usage(),
+        [path] if tokens => dump_tokens(path, edition),
+        [path] if disassemble => disassemble_file(path, settings),
+        _ if tokens || disassemble => usage(),
+        [] => repl(settings),
+        [path] => run_file(path, settings),
+        _ => usage(),
     }
-    else {
-        panic!("Usage: rlox [path]\n");
+}
+
+/// The command-line options that configure how source is compiled and
+/// errors are reported.
+#[derive(Clone, Copy)]
+struct Settings {
+    style: DiagnosticStyle,
+    edition: Edition,
+    front_end: FrontEnd,
+}
+
+impl Settings {
+    fn vm(self) -> Vm {
+        let mut vm = Vm::new();
+        vm.set_diagnostic_style(self.style);
+        vm.set_edition(self.edition);
+        vm.set_front_end(self.front_end);
+        vm
     }
 }
 
-fn repl() {
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(64);
+}
+
+fn repl(settings: Settings) {
+    let mut vm = settings.vm();
     loop {
         print!("> ");
         io::stdout().flush().unwrap();
         let mut line = String::new();
         match io::stdin().read_line(&mut line) {
-            Ok(_) => { interpret(line); },
+            Ok(0) => { println!(); break; },
+            Ok(_) => { let _ = vm.interpret(&repl_source(&line, settings.edition)); },
             Err(_) => {},
         }
     }
 }
 
-fn run_file(path: &String) {
-    let source = fs::read_to_string(path).expect("Something went wrong reading the file");
-    let _result: InterpretResult  = interpret(source);
+/// Lets the REPL evaluate a bare expression such as `1 + 2`: a line that
+/// isn't a program on its own but is one as `print <line>;` runs as that.
+fn repl_source(line: &str, edition: Edition) -> String {
+    let printed = format!("print {};", line.trim_end());
+    if parse(line, edition).is_err() && parse(&printed, edition).is_ok() { printed } else { line.to_string() }
+}
+
+fn read_file(path: &str) -> String {
+    fs::read_to_string(path).expect("Something went wrong reading the file")
+}
+
+fn run_file(path: &str, settings: Settings) {
+    if Path::new(path).extension().is_some_and(|ext| ext == "loxc") {
+        return run_bytecode(path);
+    }
+    let source = read_file(path);
+    let mut vm = settings.vm();
+    match vm.interpret(&source) {
+        Ok(()) => {},
+        Err(LoxError::CompileError(_) | LoxError::BytecodeError(_)) => process::exit(65),
+        Err(LoxError::RuntimeError(_)) => process::exit(70),
+    }
+}
+
+/// Prints every token in the file, one per line, exiting with the compile
+/// error status if any of them is an error token.
+fn dump_tokens(path: &str, edition: Edition) {
+    let source = read_file(path);
+    let mut had_error = false;
+    for token in Scanner::with_edition(source.clone(), edition) {
+        let location = format!("{}:{}", token.line, token.column);
+        print!("{:>8} {:<14} '{}'", location, format!("{:?}", token.token_type), token.text(&source));
+        if token.token_type == TokenType::Error {
+            print!(" {}", token.message);
+            had_error = true;
+        }
+        println!();
+    }
+    if had_error { process::exit(65); }
+}
+
+/// Compiles the file without running it and prints its bytecode.
+fn disassemble_file(path: &str, settings: Settings) {
+    let source = read_file(path);
+    let mut vm = settings.vm();
+    match vm.compile(&source) {
+        Ok(chunk) => disassemble_chunk(&mut io::stdout(), &chunk, "<script>"),
+        Err(_) => process::exit(65),
+    }
+}
 
-    // if (result == INTERPRET_COMPILE_ERROR) exit(65);
-    // if (result == INTERPRET_RUNTIME_ERROR) exit(70);
+/// Compiles the file to bytecode and writes it to `output`, which defaults
+/// to the source path with a `.loxc` extension.
+fn compile_file(path: &str, output: Option<String>, settings: Settings) {
+    let source = read_file(path);
+    let mut vm = settings.vm();
+    let chunk = match vm.compile(&source) {
+        Ok(chunk) => chunk,
+        Err(_) => process::exit(65),
+    };
+    let bytes = serialize(&chunk).unwrap_or_else(|error| {
+        eprintln!("Could not compile '{}': {}", path, error);
+        process::exit(65);
+    });
+    let output = output.unwrap_or_else(|| Path::new(path).with_extension("loxc").to_string_lossy().into_owned());
+    if let Err(error) = fs::write(&output, bytes) {
+        eprintln!("Could not write '{}': {}", output, error);
+        process::exit(74);
+    }
+}
+
+/// Rewrites each file in place with `format_source`. With `check`, files
+/// are left alone and the ones that would change are listed instead,
+/// exiting with status 1 if there are any.
+fn format_files(paths: &[String], check: bool, edition: Edition) {
+    let mut unformatted = false;
+    let mut had_error = false;
+    for path in paths {
+        let source = read_file(path);
+        let formatted = match format_source(&source, edition) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}: {}", path, error);
+                }
+                had_error = true;
+                continue;
+            }
+        };
+        if formatted == source { continue; }
+
+        if check {
+            println!("Would reformat '{}'", path);
+            unformatted = true;
+        }
+        else if let Err(error) = fs::write(path, formatted) {
+            eprintln!("Could not write '{}': {}", path, error);
+            process::exit(74);
+        }
+    }
+    if had_error { process::exit(65); }
+    if unformatted { process::exit(1); }
+}
+
+fn run_bytecode(path: &str) {
+    let bytes = fs::read(path).expect("Something went wrong reading the file");
+    let chunk = deserialize(&bytes).unwrap_or_else(|error| {
+        eprintln!("Could not load '{}': {}", path, error);
+        process::exit(65);
+    });
+    match Vm::new().run_chunk(chunk) {
+        Ok(()) => {},
+        Err(LoxError::CompileError(_) | LoxError::BytecodeError(_)) => process::exit(65),
+        Err(LoxError::RuntimeError(_)) => process::exit(70),
+    }
 }
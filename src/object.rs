@@ -1,7 +1,24 @@
+use crate::value::Value;
+
+/// A function implemented in Rust and called from the embedding API. It
+/// gets the call's arguments and returns its result or an error message.
+pub type NativeFn = fn(&[Value]) -> Result<Value, String>;
+
 pub struct Obj {
     pub t: ObjType,
 }
 
 pub enum ObjType {
-    ObjString,
-}
\ No newline at end of file
+    ObjString(String),
+    ObjNative { name: String, function: NativeFn },
+}
+
+impl Obj {
+    pub fn string(chars: &str) -> Obj {
+        Obj { t: ObjType::ObjString(chars.to_string()) }
+    }
+
+    pub fn native(name: &str, function: NativeFn) -> Obj {
+        Obj { t: ObjType::ObjNative { name: name.to_string(), function } }
+    }
+}
@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+use crate::error::LoxError;
+use crate::vm::Vm;
+
+/// An in-memory `Write` sink whose contents stay readable after a clone of it
+/// has been handed to a `Vm`.
+#[derive(Clone, Default)]
+pub struct SharedBuffer {
+    bytes: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    pub fn new() -> SharedBuffer {
+        SharedBuffer::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.bytes.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct CapturedRun {
+    pub stdout: String,
+    pub stderr: String,
+    pub result: Result<(), LoxError>,
+}
+
+/// Runs `source` in a fresh `Vm` and returns everything it printed.
+pub fn run_captured(source: &str) -> CapturedRun {
+    let stdout = SharedBuffer::new();
+    let stderr = SharedBuffer::new();
+    let mut vm = Vm::with_output(Box::new(stdout.clone()), Box::new(stderr.clone()));
+    let result = vm.interpret(source);
+    CapturedRun {
+        stdout: stdout.contents(),
+        stderr: stderr.contents(),
+        result,
+    }
+}
@@ -32,8 +32,9 @@ impl Parser {
             (TokenType::GreaterEqual, rule(Compiler::nil, Compiler::binary, PREC_COMPARISON)),
             (TokenType::Less, rule(Compiler::nil, Compiler::binary, PREC_COMPARISON)),
             (TokenType::LessEqual, rule(Compiler::nil, Compiler::binary, PREC_COMPARISON)),
-            (TokenType::Identifier, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Identifier, rule(Compiler::variable, Compiler::nil, PREC_NONE)),
             (TokenType::String, rule(Compiler::string, Compiler::nil, PREC_NONE)),
+            (TokenType::Interpolation, rule(Compiler::interpolation, Compiler::nil, PREC_NONE)),
             (TokenType::Number, rule(Compiler::number, Compiler::nil, PREC_NONE)),
             (TokenType::And, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
             (TokenType::Class, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
@@ -51,6 +52,13 @@ impl Parser {
             (TokenType::True, rule(Compiler::literal, Compiler::nil, PREC_NONE)),
             (TokenType::Var, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
             (TokenType::While, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Break, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Catch, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Continue, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Import, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Try, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Whitespace, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
+            (TokenType::Comment, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
             (TokenType::Error, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
             (TokenType::EOF, rule(Compiler::nil, Compiler::nil, PREC_NONE)),
 
@@ -71,17 +79,21 @@ impl Parser {
 
 pub static PREC_NONE: i32 = 1;
 pub static PREC_ASSIGNMENT: i32 = 2;
+#[allow(dead_code)]
 pub static PREC_OR: i32 = 3;
+#[allow(dead_code)]
 pub static PREC_AND: i32 = 4;
 pub static PREC_EQUALITY: i32 = 5;
 pub static PREC_COMPARISON: i32 = 6;
 pub static PREC_TERM: i32 = 7;
 pub static PREC_FACTOR: i32 = 8;
 pub static PREC_UNARY: i32 = 9;
+#[allow(dead_code)]
 pub static PREC_CALL: i32 = 10;
+#[allow(dead_code)]
 pub static PREC_PRIMARY: i32 = 11;
 
-pub type ParseFn = fn(&mut Compiler);
+pub type ParseFn = fn(&mut Compiler, bool);
 
 pub struct ParseRule {
     pub prefix: ParseFn,
@@ -0,0 +1,105 @@
+//! A peephole pass over a finished chunk. It works on whole instructions, so
+//! it never mistakes an operand byte for an opcode, and each rewrite keeps
+//! the debug info of the instruction whose runtime errors it takes over.
+
+use crate::chunk::{operand_count, Chunk, OP_ADD, OP_CONSTANT, OP_EQUAL, OP_GET_GLOBAL, OP_GET_GLOBAL_ADD_CONSTANT, OP_GREATER, OP_GREATER_EQUAL, OP_LESS, OP_LESS_EQUAL, OP_NOT, OP_NOT_EQUAL, OP_RETURN};
+use crate::scanner::Span;
+
+/// One decoded instruction: its opcode and operands, and the line, column
+/// and span of each of those bytes.
+struct Instruction {
+    code: Vec<usize>,
+    debug: Vec<(i32, usize, Span)>,
+}
+
+/// Rewrites `chunk` in place:
+///
+/// - code after the first `OP_RETURN` is dropped, since nothing can jump
+///   past it;
+/// - a comparison followed by `OP_NOT` becomes the opposite comparison;
+/// - `OP_GET_GLOBAL`, `OP_CONSTANT`, `OP_ADD` becomes one
+///   `OP_GET_GLOBAL_ADD_CONSTANT`.
+///
+/// A chunk containing an unknown opcode is left alone.
+pub fn optimize(chunk: &mut Chunk) {
+    let instructions = match decode(chunk) {
+        Some(instructions) => instructions,
+        None => return,
+    };
+
+    let mut optimized: Vec<Instruction> = Vec::with_capacity(instructions.len());
+    for instruction in instructions {
+        let returns = instruction.code[0] == OP_RETURN;
+        optimized.push(instruction);
+        fuse(&mut optimized);
+        if returns { break; }
+    }
+
+    chunk.code.clear();
+    chunk.lines.clear();
+    chunk.columns.clear();
+    chunk.spans.clear();
+    for instruction in optimized {
+        chunk.code.extend(instruction.code);
+        for (line, column, span) in instruction.debug {
+            chunk.lines.push(line);
+            chunk.columns.push(column);
+            chunk.spans.push(span);
+        }
+    }
+}
+
+fn decode(chunk: &Chunk) -> Option<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let end = offset + 1 + operand_count(chunk.code[offset])?;
+        if end > chunk.code.len() { return None; }
+        instructions.push(Instruction {
+            code: chunk.code[offset..end].to_vec(),
+            debug: (offset..end).map(|at| (chunk.lines[at], chunk.columns[at], chunk.spans[at])).collect(),
+        });
+        offset = end;
+    }
+    Some(instructions)
+}
+
+/// Applies every rewrite that matches the end of `instructions`, which has
+/// just had an instruction pushed onto it.
+fn fuse(instructions: &mut Vec<Instruction>) {
+    loop {
+        let length = instructions.len();
+        let tail: Vec<usize> = instructions[length.saturating_sub(3)..].iter().map(|instruction| instruction.code[0]).collect();
+
+        match tail.as_slice() {
+            [.., comparison, OP_NOT] if negated(*comparison).is_some() => {
+                instructions.pop();
+                let last = instructions.last_mut().unwrap();
+                last.code[0] = negated(*comparison).unwrap();
+            }
+            [OP_GET_GLOBAL, OP_CONSTANT, OP_ADD] => {
+                let add = instructions.pop().unwrap();
+                let constant = instructions.pop().unwrap();
+                let get = instructions.last_mut().unwrap();
+                get.code[0] = OP_GET_GLOBAL_ADD_CONSTANT;
+                get.code.push(constant.code[1]);
+                // The VM reports a failed add at the constant operand.
+                get.debug.push(add.debug[0]);
+            }
+            _ => return,
+        }
+    }
+}
+
+/// The comparison that gives the opposite answer to `opcode`, NaN included.
+fn negated(opcode: usize) -> Option<usize> {
+    match opcode {
+        OP_EQUAL => Some(OP_NOT_EQUAL),
+        OP_NOT_EQUAL => Some(OP_EQUAL),
+        OP_GREATER => Some(OP_LESS_EQUAL),
+        OP_LESS_EQUAL => Some(OP_GREATER),
+        OP_LESS => Some(OP_GREATER_EQUAL),
+        OP_GREATER_EQUAL => Some(OP_LESS),
+        _ => None,
+    }
+}
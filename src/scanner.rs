@@ -1,29 +1,108 @@
-// use crate::value::Value;
+/// Which set of reserved words the scanner recognizes. Later editions
+/// reserve more words, so scripts written against an earlier edition that
+/// use them as variable names keep working until they opt in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Edition {
+    /// The keywords of the original Lox language.
+    #[default]
+    Classic,
+    /// Adds `break`, `continue`, `import`, `try` and `catch`.
+    Extended,
+}
+
+/// Keywords reserved in every edition.
+const KEYWORDS: &[(&str, TokenType)] = &[
+    ("and", TokenType::And),
+    ("class", TokenType::Class),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("for", TokenType::For),
+    ("fun", TokenType::Fun),
+    ("if", TokenType::If),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+];
+
+/// Keywords reserved from `Edition::Extended` on.
+const EXTENDED_KEYWORDS: &[(&str, TokenType)] = &[
+    ("break", TokenType::Break),
+    ("catch", TokenType::Catch),
+    ("continue", TokenType::Continue),
+    ("import", TokenType::Import),
+    ("try", TokenType::Try),
+];
 
 pub struct Scanner {
     source: Vec<char>,
+    edition: Edition,
     start: usize,
     current: usize,
     line: i32,
+    /// Index of the first character on the current line.
+    line_start: usize,
+    /// The line and 1-based column `start` is at. Tokens spanning several
+    /// lines report where they start, so the pair points at their first
+    /// character.
+    start_line: i32,
+    start_column: usize,
+    start_byte: usize,
+    current_byte: usize,
+    /// Whether whitespace and comments are returned as tokens.
+    lossless: bool,
+    /// Set once the `EOF` token has been yielded by the iterator.
+    finished: bool,
+    /// One entry per `${` we are inside of, counting the unmatched `{` seen
+    /// since, so the `}` that closes the interpolation can be told apart.
+    interpolations: Vec<usize>,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Scanner {
+        Scanner::with_edition(source, Edition::Classic)
+    }
+
+    pub fn with_edition(source: String, edition: Edition) -> Scanner {
         let source = source.chars().collect();
         Scanner {
             source,
+            edition,
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            start_line: 1,
+            start_column: 1,
+            start_byte: 0,
+            current_byte: 0,
+            interpolations: Vec::new(),
+            lossless: false,
+            finished: false,
         }
     }
 
+    /// In lossless mode whitespace and comments come out as `Whitespace`
+    /// and `Comment` tokens, so concatenating the text of every token
+    /// reproduces the source exactly.
+    pub fn set_lossless(&mut self, lossless: bool) {
+        self.lossless = lossless;
+    }
+
     pub fn scan_token(&mut self) -> Token {
-        self.skip_whitespace();
+        if self.lossless {
+            if let Some(trivia) = self.trivia() { return trivia; }
+        }
+        else if let Some(error) = self.skip_whitespace() { return error; }
+        self.begin_token();
         if self.is_at_end() { return self.make_token(TokenType::EOF); }
-        self.start = self.current;
 
-        let c = self.advance().clone();
+        let c = *self.advance();
 
         if self.is_alpha(&c) { return self.identifier(); }
 
@@ -41,8 +120,21 @@ impl Scanner {
         match c {
             '(' => return self.make_token(TokenType::LeftParen),
             ')' => return self.make_token(TokenType::RightParen),
-            '{' => return self.make_token(TokenType::LeftBrace),
-            '}' => return self.make_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() { *depth += 1; }
+                return self.make_token(TokenType::LeftBrace);
+            }
+            '}' => {
+                match self.interpolations.last_mut() {
+                    Some(0) => {
+                        self.interpolations.pop();
+                        return self.string();
+                    }
+                    Some(depth) => *depth -= 1,
+                    None => {},
+                }
+                return self.make_token(TokenType::RightBrace);
+            }
             ';' => return self.make_token(TokenType::Semicolon),
             ',' => return self.make_token(TokenType::Comma),
             '.' => return self.make_token(TokenType::Dot),
@@ -58,11 +150,11 @@ impl Scanner {
             _ => {},
         }
 
-        self.error_token("Unexpected character".to_string())
+        self.error_token("Unexpected character.")
     }
 
     fn  is_at_end(&self) -> bool {
-        return self.current >= self.source.len()
+        self.current >= self.source.len()
     }
 
     fn make_token(&mut self, token_type: TokenType) -> Token {
@@ -70,48 +162,91 @@ impl Scanner {
             token_type,
             start: self.start,
             length: self.current - self.start,
-            line: self.line,
-            // message: "".to_string(),
+            line: self.start_line,
+            column: self.start_column,
+            span: self.span(),
+            message: "",
         }
     }
 
-    fn error_token(&mut self, _message: String) -> Token {
+    /// An error token spanning the offending source text, carrying `message`.
+    fn error_token(&mut self, message: &'static str) -> Token {
         Token {
             token_type: TokenType::Error,
-            start: 0,
-            length: 0,
-            line: self.line,
-            // message: message,
+            start: self.start,
+            length: self.current - self.start,
+            line: self.start_line,
+            column: self.start_column,
+            span: self.span(),
+            message,
         }
     }
 
-    fn skip_whitespace(&mut self) {
-        loop {
-            let c = self.peek();
-            match c {
-                ' ' | '\r' | '\t' => { self.advance(); }
-                '\n' => { self.line = self.line + 1; self.advance(); }
-                '/' => {
-                    let next = self.peek_next();
-                    if next == &'/' {
-                        loop {
-                            if self.is_at_end() { break; }
-                            let peeked = &self.peek().clone();
-                            if peeked == &'\n' { break; }
-                            self.advance();
-                        }
-                    }
+    /// Starts the next token at the current character.
+    fn begin_token(&mut self) {
+        self.start = self.current;
+        self.start_byte = self.current_byte;
+        self.start_line = self.line;
+        self.start_column = self.start - self.line_start + 1;
+    }
+
+    fn span(&self) -> Span {
+        Span { start: self.start_byte, end: self.current_byte }
+    }
+
+    /// Skips whitespace and comments, returning an error token if a block
+    /// comment is never closed.
+    fn skip_whitespace(&mut self) -> Option<Token> {
+        while let Some(token) = self.trivia() {
+            if token.token_type == TokenType::Error { return Some(token); }
+        }
+        None
+    }
+
+    /// Scans a run of whitespace or a single comment, if one starts here.
+    fn trivia(&mut self) -> Option<Token> {
+        self.begin_token();
+        match self.peek() {
+            ' ' | '\r' | '\t' | '\n' => {
+                while matches!(self.peek(), ' ' | '\r' | '\t' | '\n') {
+                    if self.peek() == &'\n' { self.line += 1; }
+                    self.advance();
+                }
+                Some(self.make_token(TokenType::Whitespace))
+            }
+            '/' if self.peek_next() == &'/' => {
+                while self.peek() != &'\n' && !self.is_at_end() {
+                    self.advance();
                 }
-                _ => break
+                Some(self.make_token(TokenType::Comment))
             }
+            '/' if self.peek_next() == &'*' => Some(self.block_comment()),
+            _ => None,
         }
     }
 
-    fn is_alpha(&self, c: &char) -> bool {
-        return match c {
-            'a'..='z' | 'A'..='Z' | '_' => true,
-            _ => false,
+    /// Scans a `/* ... */` comment. Block comments nest, so commenting out
+    /// code that already contains one works as expected.
+    fn block_comment(&mut self) -> Token {
+        self.advance();
+        self.advance();
+
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() { return self.error_token("Unterminated block comment."); }
+            let c = *self.advance();
+            match c {
+                '\n' => { self.line += 1; }
+                '/' if self.match_next(&'*') => { depth += 1; }
+                '*' if self.match_next(&'/') => { depth -= 1; }
+                _ => {}
+            }
         }
+        self.make_token(TokenType::Comment)
+    }
+
+    fn is_alpha(&self, c: &char) -> bool {
+        matches!(c, 'a'..='z' | 'A'..='Z' | '_')
     }
 
     fn identifier(&mut self) -> Token {
@@ -122,75 +257,116 @@ impl Scanner {
     }
 
     fn identifier_type(&self) -> TokenType {
-        match self.get_char_at_idx(self.start) {
-            'a' => self.check_keyword(1, "nd".to_string(), TokenType::And),
-            'c' => self.check_keyword(1, "lass".to_string(), TokenType::Class),
-            'e' => self.check_keyword(1, "lse".to_string(), TokenType::Else),
-            'i' => self.check_keyword(1, "f".to_string(), TokenType::If),
-            'n' => self.check_keyword(1, "il".to_string(), TokenType::Nil),
-            'o' => self.check_keyword(1, "r".to_string(), TokenType::Or),
-            'p' => self.check_keyword(1, "rint".to_string(), TokenType::Print),
-            'r' => self.check_keyword(1, "eturn".to_string(), TokenType::Return),
-            's' => self.check_keyword(1, "uper".to_string(), TokenType::Super),
-            'v' => self.check_keyword(1, "ar".to_string(), TokenType::Var),
-            'w' => self.check_keyword(1, "hile".to_string(), TokenType::While),
-            'f' => match self.get_char_at_idx(self.start + 1) {
-                'a' => self.check_keyword(2, "lse".to_string(), TokenType::False),
-                'o' => self.check_keyword(2, "r".to_string(), TokenType::For),
-                'u' => self.check_keyword(2, "n".to_string(), TokenType::Fun),
-                _ => TokenType::Identifier,
-            },
-            't' => match self.get_char_at_idx(self.start + 1) {
-                'h' => self.check_keyword(2, "is".to_string(), TokenType::This),
-                'r' => self.check_keyword(2, "ue".to_string(), TokenType::True),
-                _ => TokenType::Identifier,
-            },
-            _ => TokenType::Identifier,
-        }
-    }
-
-    fn check_keyword(&self, start: usize, rest: String, token_type: TokenType ) -> TokenType {
-        let length = self.current - self.start - start;
-        let strings_match = cmp(&self.source, self.start + start, length, rest);
-        if strings_match {
-            return token_type;
-        }
-        TokenType::Identifier
+        let text = &self.source[self.start..self.current];
+        let extended: &[(&str, TokenType)] = match self.edition {
+            Edition::Classic => &[],
+            Edition::Extended => EXTENDED_KEYWORDS,
+        };
+        KEYWORDS.iter().chain(extended)
+            .find(|(keyword, _)| keyword.len() == text.len() && keyword.chars().eq(text.iter().copied()))
+            .map_or(TokenType::Identifier, |(_, token_type)| *token_type)
     }
 
     fn is_digit(&self, c: &char) -> bool {
-        return match c {
-            '0'..='9' => true,
-            _ => false
-        }
+        c.is_ascii_digit()
     }
 
+    /// Scans a number literal: decimal with an optional fraction and
+    /// exponent (`1.5e-3`), hexadecimal (`0xFF`) or binary (`0b1010`). Digits
+    /// may be separated by single underscores (`1_000_000`). The value is
+    /// converted by the compiler.
     fn number(&mut self) -> Token {
-        while self.is_digit(self.peek()) { self.advance(); }
+        let first = *self.get_char_at_idx(self.start);
+        if first == '0' && matches!(self.peek(), 'x' | 'X' | 'b' | 'B') {
+            let is_radix_digit: fn(&char) -> bool = match self.advance() {
+                'x' | 'X' => char::is_ascii_hexdigit,
+                _ => |c| matches!(c, '0' | '1'),
+            };
+            let valid = is_radix_digit(self.peek()) && self.digits(is_radix_digit);
+            return self.finish_number(valid);
+        }
+
+        let mut valid = self.digits(char::is_ascii_digit);
 
         if self.peek() == &'.' && self.is_digit(self.peek_next()) {
             self.advance();
-            while self.is_digit(self.peek()) { self.advance(); }
+            valid &= self.digits(char::is_ascii_digit);
         }
 
-        self.make_token(TokenType::Number)
+        if matches!(self.peek(), 'e' | 'E') {
+            self.advance();
+            if matches!(self.peek(), '+' | '-') { self.advance(); }
+            valid &= self.is_digit(self.peek()) && self.digits(char::is_ascii_digit);
+        }
+
+        self.finish_number(valid)
+    }
+
+    /// Consumes the rest of a run of digits whose first digit has already
+    /// been consumed or checked. Returns false if an underscore isn't
+    /// followed by a digit.
+    fn digits(&mut self, is_digit: fn(&char) -> bool) -> bool {
+        loop {
+            if self.peek() == &'_' {
+                if !is_digit(self.peek_next()) { return false; }
+                self.advance();
+            }
+            else if is_digit(self.peek()) {
+                self.advance();
+            }
+            else {
+                return true;
+            }
+        }
     }
 
+    /// Makes the number token, or an error token covering the whole
+    /// malformed literal, e.g. `0b102`, `1__0` or `1e`.
+    fn finish_number(&mut self, valid: bool) -> Token {
+        if valid && !self.is_alpha(self.peek()) && !self.is_digit(self.peek()) {
+            return self.make_token(TokenType::Number);
+        }
+        while self.is_alpha(self.peek()) || self.is_digit(self.peek()) {
+            self.advance();
+        }
+        self.error_token("Malformed number literal.")
+    }
+
+    /// Scans a string literal, or the segment of one that follows an
+    /// interpolated expression. A segment ending in `${` becomes an
+    /// `Interpolation` token and the expression after it is scanned as
+    /// ordinary tokens; the segment ending in `"` is a `String` token.
     fn string(&mut self) -> Token {
         while self.peek() != &'"' && !self.is_at_end() {
+            // Escapes are decoded by the compiler; here we only make sure an
+            // escaped quote or `$` doesn't end the segment.
+            if self.peek() == &'\\' {
+                self.advance();
+                if self.is_at_end() { break; }
+            }
+            else if self.peek() == &'$' && self.peek_next() == &'{' {
+                self.advance();
+                self.advance();
+                self.interpolations.push(0);
+                return self.make_token(TokenType::Interpolation);
+            }
             if self.peek() == &'\n' {
-                self.line = self.line + 1;
+                self.line += 1;
             }
             self.advance();
         }
 
-        if self.is_at_end() {return self.error_token("Unterminated string".to_string())}
+        if self.is_at_end() {return self.error_token("Unterminated string.")}
         self.advance();
         self.make_token(TokenType::String)
     }
 
     pub fn advance(&mut self) -> &char {
-        self.current = self.current + 1;
+        self.current += 1;
+        if let Some(c) = self.source.get(self.current - 1) {
+            self.current_byte += c.len_utf8();
+            if *c == '\n' { self.line_start = self.current; }
+        }
         self.get_char_at_idx(self.current - 1)
     }
 
@@ -213,7 +389,7 @@ impl Scanner {
     fn match_next(&mut self, expected: &char) -> bool {
         if self.is_at_end() { return false };
         if self.peek() == expected {
-            self.current = self.current + 1;
+            self.advance();
             return true;
         }
         false
@@ -225,13 +401,43 @@ impl Scanner {
     }
 }
 
-#[derive(Copy, Clone)]
+/// Yields every token up to and including `EOF`.
+impl Iterator for Scanner {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        if self.finished { return None; }
+        let token = self.scan_token();
+        self.finished = token.token_type == TokenType::EOF;
+        Some(token)
+    }
+}
+
+/// A range of byte offsets into the source, `end` exclusive.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Token {
     pub token_type: TokenType,
     pub start: usize,
     pub length: usize,
+    /// The line and column the token starts at, even if it spans lines.
     pub line: i32,
-    // pub message: str,
+    pub column: usize,
+    pub span: Span,
+    /// Only set on `TokenType::Error` tokens.
+    pub message: &'static str,
+}
+
+impl Token {
+    /// The token's lexeme, given the source it was scanned from.
+    pub fn text<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.span.start..self.span.end]
+    }
 }
 
 #[derive(Hash, Clone, Copy, Debug, PartialEq, Eq)]
@@ -248,33 +454,30 @@ pub enum TokenType {
     Less, LessEqual,
     // Literals.
     Identifier, String, Number,
+    /// A string segment ending in `${`, e.g. `"a ${` or `} b ${`.
+    Interpolation,
     // Keywords.
     And, Class, Else, False,
     For, Fun, If, Nil, Or,
     Print, Return, Super, This,
     True, Var, While,
+    // Keywords of `Edition::Extended`.
+    Break, Catch, Continue, Import, Try,
+    // Trivia, only produced in lossless mode.
+    Whitespace, Comment,
     // Util
     Error, EOF, Empty
 }
 
-fn cmp(a: &Vec<char>, a_start: usize, length: usize, b: String) -> bool {
-    if b.len() != length { return false; }
-    let b_chars: Vec<char> = b.chars().collect();
-    for i in 0..length {
-        if a.get(i + a_start) != b_chars.get(i) {
-            return false;
-        }
-    }
-    true
-}
-
 pub fn make_empty_token() -> Token {
     Token {
         token_type: TokenType::Empty,
         start: 0,
         length: 0,
         line: 0,
-        // message: "A token about nothing".to_string(),
+        column: 0,
+        span: Span::default(),
+        message: "",
     }
 }
 
@@ -1,6 +1,8 @@
+use std::convert::TryFrom;
 use std::fmt;
-use crate::value::ValueType::Bool;
-use crate::value::ValueType::Obj;
+use std::mem::ManuallyDrop;
+use std::rc::Rc;
+use crate::object::{NativeFn, Obj, ObjType};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ValueType {
@@ -10,17 +12,43 @@ pub enum ValueType {
     Obj,
 }
 
-#[derive(Clone, Copy)]
-pub union ValueData {
-    pub boolean: bool,
-    pub number: f64,
-    pub obj: ValueType,
+/// Which field of the union is live is recorded in `Value::value_type`, so
+/// neither is exposed: outside this module values are only read through the
+/// checked `as_*` accessors.
+union ValueData {
+    boolean: bool,
+    number: f64,
+    obj: ManuallyDrop<Rc<Obj>>,
 }
 
-#[derive(Clone, Copy)]
 pub struct Value {
-    pub value_type: ValueType,
-    pub read_as: ValueData,
+    value_type: ValueType,
+    read_as: ValueData,
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+}
+
+impl Clone for Value {
+    fn clone(&self) -> Value {
+        match self.value_type {
+            ValueType::Bool => bool_val(unsafe { self.read_as.boolean }),
+            ValueType::Nil => NIL_VAL,
+            ValueType::Number => number_val(unsafe { self.read_as.number }),
+            ValueType::Obj => obj_val(Rc::clone(unsafe { &self.read_as.obj })),
+        }
+    }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        if self.value_type == ValueType::Obj {
+            unsafe { ManuallyDrop::drop(&mut self.read_as.obj) }
+        }
+    }
 }
 
 impl fmt::Debug for Value {
@@ -32,33 +60,114 @@ impl fmt::Debug for Value {
     }
 }
 
-impl Value {
-    pub fn to_string(self) -> String {
-        match self.value_type {
-            ValueType::Bool => if as_bool(&self) { return "true".to_string(); } else { return "false".to_string(); },
-            ValueType::Nil => "nil".to_string(),
-            ValueType::Number => format!("{}", as_number(&self)),
-            ValueType::Obj => "string".to_string(),
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(boolean) = as_bool(self) { return write!(f, "{}", boolean); }
+        if let Some(number) = as_number(self) { return write!(f, "{}", number); }
+        match as_obj(self) {
+            Some(obj) => match &obj.t {
+                ObjType::ObjString(chars) => write!(f, "{}", chars),
+                ObjType::ObjNative { name, .. } => write!(f, "<native fn {}>", name),
+            },
+            None => write!(f, "nil"),
         }
     }
 }
 
-pub fn print_value(value: Value) {
-    print!("{}", value.to_string());
+impl From<f64> for Value {
+    fn from(number: f64) -> Value {
+        number_val(number)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(boolean: bool) -> Value {
+        bool_val(boolean)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(chars: &str) -> Value {
+        string_val(chars)
+    }
+}
+
+/// Returned when a `Value` is converted to a Rust type it doesn't hold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TypeMismatch {
+    pub expected: ValueType,
+    pub found: ValueType,
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {:?} value, found {:?}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for TypeMismatch {}
+
+impl TryFrom<Value> for f64 {
+    type Error = TypeMismatch;
+
+    fn try_from(value: Value) -> Result<f64, TypeMismatch> {
+        as_number(&value).ok_or(TypeMismatch { expected: ValueType::Number, found: value.value_type })
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = TypeMismatch;
+
+    fn try_from(value: Value) -> Result<bool, TypeMismatch> {
+        as_bool(&value).ok_or(TypeMismatch { expected: ValueType::Bool, found: value.value_type })
+    }
 }
 
-pub fn as_number(value: &Value) -> f64 {
-    unsafe {
-        value.read_as.number
+impl TryFrom<Value> for String {
+    type Error = TypeMismatch;
+
+    fn try_from(value: Value) -> Result<String, TypeMismatch> {
+        match as_string(&value) {
+            Some(chars) => Ok(chars.to_string()),
+            None => Err(TypeMismatch { expected: ValueType::Obj, found: value.value_type }),
+        }
     }
 }
 
-pub fn as_bool(value: &Value) -> bool {
-    unsafe {
-        value.read_as.boolean
+pub fn as_number(value: &Value) -> Option<f64> {
+    if value.value_type != ValueType::Number { return None; }
+    Some(unsafe { value.read_as.number })
+}
+
+pub fn as_bool(value: &Value) -> Option<bool> {
+    if value.value_type != ValueType::Bool { return None; }
+    Some(unsafe { value.read_as.boolean })
+}
+
+pub fn as_obj(value: &Value) -> Option<&Rc<Obj>> {
+    if value.value_type != ValueType::Obj { return None; }
+    Some(unsafe { &value.read_as.obj })
+}
+
+pub fn as_string(value: &Value) -> Option<&str> {
+    match &as_obj(value)?.t {
+        ObjType::ObjString(chars) => Some(chars),
+        _ => None,
     }
 }
 
+/// The length of a string value in Unicode scalar values, so `"héllo"` has
+/// length 5 whatever its UTF-8 encoding takes.
+pub fn string_length(value: &Value) -> Option<usize> {
+    as_string(value).map(|chars| chars.chars().count())
+}
+
+/// The Unicode scalar value at `index` in a string value, counting scalar
+/// values rather than bytes.
+pub fn string_char_at(value: &Value, index: usize) -> Option<char> {
+    as_string(value).and_then(|chars| chars.chars().nth(index))
+}
+
 pub fn number_val(number: f64) -> Value {
     Value {
         value_type: ValueType::Number,
@@ -67,17 +176,25 @@ pub fn number_val(number: f64) -> Value {
         }
     }
 }
-//
-// pub fn string_val(string: str) -> Value {
-//     Value {
-//         value_type: ValueType::Obj,
-//         read_as: ValueData {
-//             number
-//         }
-//     }
-// }
 
-pub static NIL_VAL: Value = Value {
+pub fn obj_val(obj: Rc<Obj>) -> Value {
+    Value {
+        value_type: ValueType::Obj,
+        read_as: ValueData {
+            obj: ManuallyDrop::new(obj)
+        }
+    }
+}
+
+pub fn string_val(chars: &str) -> Value {
+    obj_val(Rc::new(Obj::string(chars)))
+}
+
+pub fn native_val(name: &str, function: NativeFn) -> Value {
+    obj_val(Rc::new(Obj::native(name, function)))
+}
+
+pub const NIL_VAL: Value = Value {
     value_type: ValueType::Nil,
     read_as: ValueData {
         number: 0.0,
@@ -85,5 +202,25 @@ pub static NIL_VAL: Value = Value {
 };
 
 pub fn bool_val(boolean: bool) -> Value {
-    Value { value_type: ValueType::Bool, read_as: ValueData { boolean: boolean } }
+    Value { value_type: ValueType::Bool, read_as: ValueData { boolean } }
+}
+
+/// `nil` and `false` are falsy; every other value is truthy.
+pub fn is_falsy(value: &Value) -> bool {
+    value.value_type == ValueType::Nil || as_bool(value) == Some(false)
+}
+
+/// Lox `==`: values of different types are never equal, strings compare by
+/// content and other objects by identity.
+pub fn values_equal(a: &Value, b: &Value) -> bool {
+    if a.value_type != b.value_type { return false; }
+    match a.value_type {
+        ValueType::Bool => as_bool(a) == as_bool(b),
+        ValueType::Nil => true,
+        ValueType::Number => as_number(a) == as_number(b),
+        ValueType::Obj => match (as_string(a), as_string(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => matches!((as_obj(a), as_obj(b)), (Some(a), Some(b)) if Rc::ptr_eq(a, b)),
+        },
+    }
 }
@@ -0,0 +1,85 @@
+//! Checks that a chunk is safe to hand to the VM. The compiler only emits
+//! well-formed code, but chunks loaded from `.loxc` files or built by hand
+//! could otherwise make `run` index out of bounds or misread a constant.
+
+use std::collections::HashMap;
+
+use crate::chunk::{operand_count, Chunk, OP_ADD, OP_CONSTANT, OP_DEFINE_GLOBAL, OP_DIVIDE, OP_EQUAL, OP_FALSE, OP_GET_GLOBAL, OP_GREATER, OP_LESS, OP_MULTIPLY, OP_NEGATE, OP_NIL, OP_NOT, OP_POP, OP_PRINT, OP_RETURN, OP_SET_GLOBAL, OP_SUBTRACT, OP_TO_STRING, OP_TRUE, OP_NOT_EQUAL, OP_GREATER_EQUAL, OP_LESS_EQUAL, OP_GET_GLOBAL_ADD_CONSTANT};
+use crate::error::BytecodeError;
+use crate::value::as_string;
+
+/// How an instruction changes the stack. Its operands, all constant
+/// indexes, are counted by `operand_count`.
+struct Effect {
+    /// Whether the first operand must name a string, i.e. a variable name.
+    names_variable: bool,
+    pops: usize,
+    pushes: usize,
+    /// Whether execution continues at the next instruction.
+    falls_through: bool,
+}
+
+fn effect(opcode: usize) -> Option<Effect> {
+    let simple = |pops, pushes| Effect { names_variable: false, pops, pushes, falls_through: true };
+    let variable = |pops, pushes| Effect { names_variable: true, pops, pushes, falls_through: true };
+    let effect = match opcode {
+        OP_CONSTANT | OP_NIL | OP_TRUE | OP_FALSE => simple(0, 1),
+        OP_GET_GLOBAL | OP_GET_GLOBAL_ADD_CONSTANT => variable(0, 1),
+        OP_SET_GLOBAL => variable(1, 1),
+        OP_DEFINE_GLOBAL => variable(1, 0),
+        OP_POP | OP_PRINT => simple(1, 0),
+        OP_NOT | OP_NEGATE | OP_TO_STRING => simple(1, 1),
+        OP_EQUAL | OP_NOT_EQUAL | OP_GREATER | OP_GREATER_EQUAL | OP_LESS | OP_LESS_EQUAL | OP_ADD | OP_SUBTRACT | OP_MULTIPLY | OP_DIVIDE => simple(2, 1),
+        OP_RETURN => Effect { names_variable: false, pops: 0, pushes: 0, falls_through: false },
+        _ => return None,
+    };
+    Some(effect)
+}
+
+/// Walks every path through `chunk` from its first instruction, checking
+/// that each opcode is known, its operand is in bounds, no path runs off the
+/// end of the code, and the stack never underflows and has the same depth
+/// whichever path reaches an instruction.
+pub fn verify_chunk(chunk: &Chunk) -> Result<(), BytecodeError> {
+    let length = chunk.code.len();
+    if chunk.lines.len() != length || chunk.columns.len() != length || chunk.spans.len() != length {
+        return Err(BytecodeError { message: "Debug info doesn't cover every byte of code.".to_string() });
+    }
+
+    let mut depths: HashMap<usize, usize> = HashMap::new();
+    let mut pending = vec![(0, 0)];
+
+    while let Some((offset, depth)) = pending.pop() {
+        let invalid = |message: String| BytecodeError { message: format!("Invalid bytecode at offset {}: {}", offset, message) };
+
+        if offset >= length {
+            return Err(invalid("execution runs past the end of the code.".to_string()));
+        }
+        match depths.get(&offset) {
+            Some(seen) if *seen == depth => continue,
+            Some(seen) => return Err(invalid(format!("stack depth is {} on one path and {} on another.", seen, depth))),
+            None => { depths.insert(offset, depth); }
+        }
+
+        let opcode = chunk.code[offset];
+        let (operands, effect) = operand_count(opcode).zip(effect(opcode))
+            .ok_or_else(|| invalid(format!("unknown opcode {}.", opcode)))?;
+
+        let mut next = offset + 1;
+        for operand in 0..operands {
+            let index = *chunk.code.get(next).ok_or_else(|| invalid("missing operand.".to_string()))?;
+            let constant = chunk.constants.get(index)
+                .ok_or_else(|| invalid(format!("constant {} is out of bounds.", index)))?;
+            if operand == 0 && effect.names_variable && as_string(constant).is_none() {
+                return Err(invalid(format!("constant {} is not a variable name.", index)));
+            }
+            next += 1;
+        }
+
+        let depth = depth.checked_sub(effect.pops).ok_or_else(|| invalid("stack underflow.".to_string()))?;
+        if effect.falls_through {
+            pending.push((next, depth + effect.pushes));
+        }
+    }
+    Ok(())
+}
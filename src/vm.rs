@@ -1,140 +1,342 @@
-use crate::chunk::{Chunk, OP_RETURN, OP_CONSTANT, OP_NEGATE, OP_ADD, OP_SUBTRACT, OP_MULTIPLY, OP_DIVIDE, init_chunk, OP_NIL, OP_TRUE, OP_FALSE, OP_NOT, OP_EQUAL, OP_GREATER, OP_LESS};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use crate::chunk::{Chunk, OP_RETURN, OP_CONSTANT, OP_NEGATE, OP_ADD, OP_SUBTRACT, OP_MULTIPLY, OP_DIVIDE, init_chunk, OP_NIL, OP_TRUE, OP_FALSE, OP_NOT, OP_EQUAL, OP_GREATER, OP_LESS, OP_GET_GLOBAL, OP_TO_STRING, OP_PRINT, OP_POP, OP_DEFINE_GLOBAL, OP_SET_GLOBAL, OP_NOT_EQUAL, OP_GREATER_EQUAL, OP_LESS_EQUAL, OP_GET_GLOBAL_ADD_CONSTANT};
+use crate::ast::FrontEnd;
+use crate::ast_parser::parse;
+use crate::codegen::generate;
 use crate::compiler::Compiler;
-use crate::value::{as_bool, as_number, bool_val, NIL_VAL, number_val, print_value, Value, ValueData, ValueType};
+use crate::diagnostic::{render_error, DiagnosticStyle};
+use crate::error::{LoxError, RuntimeError, StackFrame};
+use crate::scanner::Edition;
+use crate::verifier::verify_chunk;
+use crate::object::{NativeFn, ObjType};
+use crate::value::{as_number, as_obj, native_val, as_string, bool_val, is_falsy, NIL_VAL, number_val, string_val, Value, values_equal};
 use crate::debug::disassemble_instruction;
 
-pub const DEBUG_TRACE_EXECUTION: bool = true;
+/// Traces every instruction to the trace sink. Enable it with the
+/// `trace-execution` feature rather than editing it here.
+pub const DEBUG_TRACE_EXECUTION: bool = cfg!(feature = "trace-execution");
 
-pub(crate) type InterpretResult = usize;
-pub const INTERPRET_OK: InterpretResult = 0;
-pub const INTERPRET_COMPILE_ERROR: InterpretResult = 1;
-pub const INTERPRET_RUNTIME_ERROR: InterpretResult = 2;
-
-pub struct VM {
+pub struct Vm {
     chunk: Chunk,
+    ip: usize,
     stack: Vec<Value>,
+    globals: HashMap<String, Value>,
+    out: Box<dyn Write>,
+    err: Box<dyn Write>,
+    trace: Box<dyn Write>,
+    diagnostic_style: DiagnosticStyle,
+    edition: Edition,
+    front_end: FrontEnd,
 }
 
-pub fn interpret(source: String) -> InterpretResult {
-    let chunk = init_chunk();
-    let mut compiler = Compiler::new(source, chunk);
-    if !compiler.compile() { return INTERPRET_COMPILE_ERROR; }
-    let vm = VM {
-        chunk: compiler.compiling_chunk,
-        stack: Vec::new(),
-    };
-    return run(vm)
+impl Default for Vm {
+    fn default() -> Vm {
+        Vm::new()
+    }
 }
 
-// , l: &dyn Fn(Value, Value) -> Value
-fn bin_op(mut stack: Vec<Value>, op_fn: &dyn Fn(f64, f64) -> f64) -> (Vec<Value>, InterpretResult) {
-    match stack.pop() {
-        None => return (stack, INTERPRET_RUNTIME_ERROR),
-        Some(b) => {
-            match stack.pop() {
-                None => return (stack, INTERPRET_RUNTIME_ERROR),
-                Some(a) => {
-                    stack.push(number_val(op_fn(as_number(&a), as_number(&b))));
-                }
-            }
+impl Vm {
+    pub fn new() -> Vm {
+        Vm::with_output(Box::new(io::stdout()), Box::new(io::stderr()))
+    }
+
+    /// Creates a VM that prints program output to `out` and reports
+    /// compile and runtime errors to `err`. Execution traces go to the
+    /// process's stderr until `set_trace` redirects them.
+    pub fn with_output(out: Box<dyn Write>, err: Box<dyn Write>) -> Vm {
+        Vm {
+            chunk: init_chunk(),
+            ip: 0,
+            stack: Vec::new(),
+            globals: HashMap::new(),
+            out,
+            err,
+            trace: Box::new(io::stderr()),
+            diagnostic_style: DiagnosticStyle::Short,
+            edition: Edition::Classic,
+            front_end: FrontEnd::SinglePass,
         }
     }
-    (stack, INTERPRET_OK)
-}
 
-fn run(vm: VM) -> InterpretResult {
-    let mut inst_idx = 0;
-    let mut stack = vm.stack;
-    while inst_idx < vm.chunk.code.len() {
+    pub fn set_output(&mut self, out: Box<dyn Write>) {
+        self.out = out;
+    }
+
+    pub fn set_diagnostics(&mut self, err: Box<dyn Write>) {
+        self.err = err;
+    }
+
+    /// Sets where `DEBUG_TRACE_EXECUTION` writes the stack and each
+    /// instruction, kept apart from diagnostics so traces never end up in
+    /// captured error output.
+    pub fn set_trace(&mut self, trace: Box<dyn Write>) {
+        self.trace = trace;
+    }
+
+    pub fn set_diagnostic_style(&mut self, style: DiagnosticStyle) {
+        self.diagnostic_style = style;
+    }
+
+    /// Selects the keyword set scripts are compiled with.
+    pub fn set_edition(&mut self, edition: Edition) {
+        self.edition = edition;
+    }
+
+    /// Selects whether source is compiled in one pass or through the AST.
+    pub fn set_front_end(&mut self, front_end: FrontEnd) {
+        self.front_end = front_end;
+    }
+
+    pub fn interpret(&mut self, source: &str) -> Result<(), LoxError> {
+        let result = self.compile_source(source).and_then(|chunk| self.execute(chunk));
+        self.report(source, result)
+    }
+
+    /// Compiles `source` without running it, reporting any compile errors
+    /// on the diagnostics sink.
+    pub fn compile(&mut self, source: &str) -> Result<Chunk, LoxError> {
+        let result = self.compile_source(source);
+        self.report(source, result)
+    }
 
-        if DEBUG_TRACE_EXECUTION {
-            println!("        {:?}", stack);
-            disassemble_instruction(&vm.chunk, inst_idx);
+    /// Runs an already compiled chunk, e.g. one loaded from a `.loxc`
+    /// file, after checking it with `verify_chunk`. There is no source to
+    /// quote, so errors are always reported in the short style.
+    pub fn run_chunk(&mut self, chunk: Chunk) -> Result<(), LoxError> {
+        let style = self.diagnostic_style;
+        self.diagnostic_style = DiagnosticStyle::Short;
+        let result = match verify_chunk(&chunk) {
+            Ok(()) => self.execute(chunk),
+            Err(error) => Err(LoxError::BytecodeError(error)),
+        };
+        let result = self.report("", result);
+        self.diagnostic_style = style;
+        result
+    }
+
+    fn compile_source(&self, source: &str) -> Result<Chunk, LoxError> {
+        if self.front_end == FrontEnd::Ast {
+            return parse(source, self.edition)
+                .and_then(|program| generate(&program, source))
+                .map_err(LoxError::CompileError);
         }
+        let mut compiler = Compiler::new(source.to_string(), init_chunk(), self.edition);
+        if !compiler.compile() { return Err(LoxError::CompileError(compiler.errors)); }
+        Ok(compiler.compiling_chunk)
+    }
 
-        let instruction = vm.chunk.code[inst_idx];
+    fn execute(&mut self, chunk: Chunk) -> Result<(), LoxError> {
+        self.chunk = chunk;
+        self.ip = 0;
+        self.stack.clear();
+        self.run().map_err(LoxError::RuntimeError)
+    }
 
-        if instruction == OP_CONSTANT {
-            inst_idx += 1;
-            let const_idx = vm.chunk.code[inst_idx];
-            let constant = vm.chunk.constants[const_idx];
-            stack.push(constant);
+    /// Renders the error in `result`, if any, and flushes every sink.
+    fn report<T>(&mut self, source: &str, result: Result<T, LoxError>) -> Result<T, LoxError> {
+        if let Err(error) = &result {
+            let _ = write!(self.err, "{}", render_error(source, error, self.diagnostic_style));
         }
+        let _ = self.out.flush();
+        let _ = self.err.flush();
+        let _ = self.trace.flush();
+        result
+    }
+
+    /// Defines the global `name` as a function implemented in Rust.
+    pub fn define_native(&mut self, name: &str, function: NativeFn) {
+        self.globals.insert(name.to_string(), native_val(name, function));
+    }
 
-        if instruction == OP_NIL { stack.push(NIL_VAL); }
-        if instruction == OP_TRUE { stack.push(bool_val(true)); }
-        if instruction == OP_FALSE { stack.push(bool_val(false)); }
-        if instruction == OP_EQUAL {
-            let b = stack.pop().unwrap();
-            let a = stack.pop().unwrap();
-            stack.push(bool_val(values_equal(a, b)));
+    /// Calls the global `name` with `args`. Only native functions can be
+    /// called until Lox functions land.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, LoxError> {
+        let function = match self.globals.get(name) {
+            None => return Err(call_error(format!("Undefined variable '{}'.", name))),
+            Some(value) => match as_obj(value).map(|obj| &obj.t) {
+                Some(ObjType::ObjNative { function, .. }) => *function,
+                _ => return Err(call_error("Can only call functions and classes.".to_string())),
+            },
+        };
+        function(args).map_err(call_error)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    pub fn set_global<V: Into<Value>>(&mut self, name: &str, value: V) {
+        self.globals.insert(name.to_string(), value.into());
+    }
+
+    fn runtime_error(&self, message: &str) -> RuntimeError {
+        let offset = self.ip.min(self.chunk.code.len().saturating_sub(1));
+        RuntimeError {
+            message: message.to_string(),
+            stack_trace: vec![StackFrame {
+                function: "script".to_string(),
+                line: self.chunk.lines.get(offset).copied().unwrap_or(0),
+                column: self.chunk.columns.get(offset).copied().unwrap_or(0),
+                span: self.chunk.spans.get(offset).copied().unwrap_or_default(),
+            }],
         }
-        if instruction == OP_GREATER {
-            let b = stack.pop().unwrap();
-            let a = stack.pop().unwrap();
-            stack.push(bool_val(as_number(&a) > as_number(&b)));
+    }
+
+    fn pop(&mut self) -> Result<Value, RuntimeError> {
+        match self.stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(self.runtime_error("Stack underflow.")),
         }
-        if instruction == OP_LESS {
-            let b = stack.pop().unwrap();
-            let a = stack.pop().unwrap();
-            stack.push(bool_val(as_number(&a) < as_number(&b)));
+    }
+
+    /// The value `distance` slots below the top of the stack, left in place.
+    fn peek(&self, distance: usize) -> Result<Value, RuntimeError> {
+        match self.stack.len().checked_sub(distance + 1) {
+            Some(idx) => Ok(self.stack[idx].clone()),
+            None => Err(self.runtime_error("Stack underflow.")),
         }
+    }
 
-        // TODO: Not idiomatic way of skipping if no match
-        let none = |_a: f64, _b: f64| 0.0;
-        let op_fn = match instruction {
-            OP_ADD => |a, b| a + b,
-            OP_SUBTRACT => |a, b| a - b,
-            OP_MULTIPLY => |a, b| a * b,
-            OP_DIVIDE  => |a, b| a / b,
-            _ => none,
-        };
-        if op_fn != none {
-            let r = bin_op(stack, &op_fn);
-            stack = r.0;
-            if r.1 == INTERPRET_RUNTIME_ERROR {
-                return r.1;
-            }
+    fn read_constant(&mut self) -> Value {
+        self.ip += 1;
+        self.chunk.constants[self.chunk.code[self.ip]].clone()
+    }
+
+    fn binary_op(&mut self, op_fn: fn(f64, f64) -> Value) -> Result<(), RuntimeError> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        match (as_number(&a), as_number(&b)) {
+            (Some(a), Some(b)) => self.stack.push(op_fn(a, b)),
+            _ => return Err(self.runtime_error("Operands must be numbers.")),
         }
+        Ok(())
+    }
 
-        else if instruction == OP_NOT {
-            let value = stack.pop().unwrap();
-            stack.push(bool_val(is_falsy(&value)))
+    fn add(&mut self) -> Result<(), RuntimeError> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        if let (Some(a), Some(b)) = (as_string(&a), as_string(&b)) {
+            self.stack.push(string_val(&(a.to_string() + b)));
         }
+        else if let (Some(a), Some(b)) = (as_number(&a), as_number(&b)) {
+            self.stack.push(number_val(a + b));
+        }
+        else {
+            return Err(self.runtime_error("Operands must be two numbers or two strings."));
+        }
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        while self.ip < self.chunk.code.len() {
 
-        else if instruction == OP_NEGATE {
-            match stack.pop() {
-                None => return INTERPRET_RUNTIME_ERROR,
-                Some(value) => stack.push(number_val(-as_number(&value))),
+            if DEBUG_TRACE_EXECUTION {
+                let _ = writeln!(self.trace, "        {:?}", self.stack);
+                disassemble_instruction(&mut self.trace, &self.chunk, self.ip);
             }
-        }
 
-        else if instruction == OP_RETURN {
-            match stack.pop() {
-                None => return INTERPRET_RUNTIME_ERROR,
-                Some(value) => {
-                    print_value(value);
-                    print!("\n");
-                    return INTERPRET_OK;
+            let instruction = self.chunk.code[self.ip];
+
+            match instruction {
+                OP_CONSTANT => {
+                    let constant = self.read_constant();
+                    self.stack.push(constant);
+                }
+                OP_NIL => self.stack.push(NIL_VAL),
+                OP_TRUE => self.stack.push(bool_val(true)),
+                OP_FALSE => self.stack.push(bool_val(false)),
+                OP_GET_GLOBAL => {
+                    let name = self.read_constant();
+                    let name = as_string(&name).unwrap_or_default();
+                    match self.globals.get(name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.runtime_error(&format!("Undefined variable '{}'.", name))),
+                    }
+                }
+                OP_DEFINE_GLOBAL => {
+                    let name = self.read_constant();
+                    let value = self.pop()?;
+                    self.globals.insert(as_string(&name).unwrap_or_default().to_string(), value);
+                }
+                OP_SET_GLOBAL => {
+                    let name = self.read_constant();
+                    let name = as_string(&name).unwrap_or_default();
+                    let value = self.peek(0)?;
+                    match self.globals.get_mut(name) {
+                        Some(global) => *global = value,
+                        None => return Err(self.runtime_error(&format!("Undefined variable '{}'.", name))),
+                    }
+                }
+                OP_GET_GLOBAL_ADD_CONSTANT => {
+                    let name = self.read_constant();
+                    let name = as_string(&name).unwrap_or_default();
+                    match self.globals.get(name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.runtime_error(&format!("Undefined variable '{}'.", name))),
+                    }
+                    let constant = self.read_constant();
+                    self.stack.push(constant);
+                    self.add()?;
+                }
+                OP_EQUAL => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_val(values_equal(&a, &b)));
+                }
+                OP_NOT_EQUAL => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_val(!values_equal(&a, &b)));
                 }
+                OP_GREATER => self.binary_op(|a, b| bool_val(a > b))?,
+                OP_LESS => self.binary_op(|a, b| bool_val(a < b))?,
+                // Negated rather than `>=` and `<=` so NaN compares the same
+                // as the `OP_LESS OP_NOT` and `OP_GREATER OP_NOT` they replace.
+                OP_GREATER_EQUAL => self.binary_op(|a, b| bool_val(a.partial_cmp(&b) != Some(Ordering::Less)))?,
+                OP_LESS_EQUAL => self.binary_op(|a, b| bool_val(a.partial_cmp(&b) != Some(Ordering::Greater)))?,
+                OP_ADD => self.add()?,
+                OP_SUBTRACT => self.binary_op(|a, b| number_val(a - b))?,
+                OP_MULTIPLY => self.binary_op(|a, b| number_val(a * b))?,
+                OP_DIVIDE => self.binary_op(|a, b| number_val(a / b))?,
+                OP_NOT => {
+                    let value = self.pop()?;
+                    self.stack.push(bool_val(is_falsy(&value)))
+                }
+                OP_TO_STRING => {
+                    let value = self.pop()?;
+                    if as_string(&value).is_some() {
+                        self.stack.push(value);
+                    }
+                    else {
+                        self.stack.push(string_val(&value.to_string()));
+                    }
+                }
+                OP_NEGATE => {
+                    let value = self.pop()?;
+                    match as_number(&value) {
+                        Some(number) => self.stack.push(number_val(-number)),
+                        None => return Err(self.runtime_error("Operand must be a number.")),
+                    }
+                }
+                OP_PRINT => {
+                    let value = self.pop()?;
+                    let _ = writeln!(self.out, "{}", value);
+                }
+                OP_POP => { self.pop()?; }
+                OP_RETURN => return Ok(()),
+                _ => return Err(self.runtime_error(&format!("Unknown opcode {}.", instruction))),
             }
-        }
 
-        inst_idx += 1;
+            self.ip += 1;
+        }
+        Err(self.runtime_error("Ran past the end of the chunk."))
     }
-    return INTERPRET_RUNTIME_ERROR;
 }
 
-fn is_falsy(value: &Value) -> bool {
-    value.value_type == ValueType::Nil || (value.value_type == ValueType::Bool && !as_bool(&value))
+/// A runtime error raised by `Vm::call`, outside any running script.
+fn call_error(message: String) -> LoxError {
+    LoxError::RuntimeError(RuntimeError { message, stack_trace: Vec::new() })
 }
-
-fn values_equal(a: Value, b: Value) -> bool {
-    if a.value_type != b.value_type { return false; }
-    match a.value_type {
-        ValueType::Bool => as_bool(&a) == as_bool(&b),
-        ValueType::Nil => true,
-        ValueType::Number => as_number(&a) == as_number(&b),
-        ValueType::Obj => true,
-    }
-}
\ No newline at end of file
@@ -0,0 +1,160 @@
+use std::convert::TryFrom;
+use std::io;
+
+use rlox::scanner::Span;
+use rlox::value::{string_char_at, string_length};
+use rlox::vm::DEBUG_TRACE_EXECUTION;
+use rlox::{DiagnosticStyle, Edition, LoxError, SharedBuffer, Value, Vm};
+
+fn quiet_vm() -> Vm {
+    Vm::with_output(Box::new(io::sink()), Box::new(io::sink()))
+}
+
+#[test]
+fn compile_errors_point_at_the_offending_token() {
+    let source = "1 +\n  (2 *);";
+    match quiet_vm().interpret(source) {
+        Err(LoxError::CompileError(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].line, 2);
+            assert_eq!(errors[0].column, 7);
+            assert_eq!(errors[0].lexeme, ")");
+            assert_eq!(errors[0].span, Span { start: 10, end: 11 });
+        }
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn spans_are_byte_offsets() {
+    let source = "\"héllo\" @;";
+    match quiet_vm().interpret(source) {
+        Err(LoxError::CompileError(errors)) => {
+            assert_eq!(errors[0].column, 9);
+            assert_eq!(errors[0].span, Span { start: 9, end: 10 });
+            assert_eq!(&source[errors[0].span.start..errors[0].span.end], "@");
+        }
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn runtime_errors_record_the_operator_location() {
+    match quiet_vm().interpret("print 1 ==\n   -\"a\";") {
+        Err(LoxError::RuntimeError(error)) => {
+            let frame = &error.stack_trace[0];
+            assert_eq!((frame.line, frame.column), (2, 4));
+            assert_eq!(frame.span, Span { start: 14, end: 15 });
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn plain_diagnostics_underline_the_span() {
+    let stderr = SharedBuffer::new();
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(stderr.clone()));
+    vm.set_diagnostic_style(DiagnosticStyle::Plain);
+    let _ = vm.interpret("print 1 +\n  (2 = 3);");
+
+    assert_eq!(stderr.contents(), "\
+error: Invalid assignment target.
+ --> 2:6
+  |
+2 |   (2 = 3);
+  |      ^
+  = help: use '==' to compare values; '=' is assignment
+");
+}
+
+#[test]
+fn traces_stay_out_of_diagnostics() {
+    let (stderr, trace) = (SharedBuffer::new(), SharedBuffer::new());
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(stderr.clone()));
+    vm.set_trace(Box::new(trace.clone()));
+    let _ = vm.interpret("print -true;");
+
+    assert_eq!(stderr.contents(), "Operand must be a number.\n[line 1] in script\n");
+    assert_eq!(trace.contents().contains("OP_NEGATE"), DEBUG_TRACE_EXECUTION);
+}
+
+#[test]
+fn strings_are_indexed_by_scalar_value() {
+    let value = Value::from("héllo😀");
+    assert_eq!(string_length(&value), Some(6));
+    assert_eq!(string_char_at(&value, 1), Some('é'));
+    assert_eq!(string_char_at(&value, 5), Some('😀'));
+    assert_eq!(string_char_at(&value, 6), None);
+    assert_eq!(string_length(&Value::from(1.0)), None);
+
+    // Escapes decode to scalar values too, and a decomposed "é" is two.
+    let mut vm = quiet_vm();
+    vm.interpret("var s = \"e\\u{301}\\u{1F600}\";").unwrap();
+    let value = vm.get_global("s").unwrap();
+    assert_eq!(string_length(&value), Some(3));
+    assert_eq!(string_char_at(&value, 1), Some('\u{301}'));
+}
+
+#[test]
+fn extended_edition_reserves_more_keywords() {
+    match quiet_vm().interpret("print break;") {
+        Err(LoxError::RuntimeError(error)) => assert_eq!(error.message, "Undefined variable 'break'."),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+
+    let mut vm = quiet_vm();
+    vm.set_edition(Edition::Extended);
+    match vm.interpret("print break;") {
+        Err(LoxError::CompileError(errors)) => assert_eq!(errors[0].message, "Expect expression."),
+        other => panic!("expected a compile error, got {:?}", other),
+    }
+}
+
+#[test]
+fn values_convert_to_and_from_rust_types() {
+    use rlox::value::{as_bool, as_number, as_obj, ValueType};
+
+    let number = Value::from(1.5);
+    assert_eq!(number.value_type(), ValueType::Number);
+    assert_eq!(as_number(&number), Some(1.5));
+    assert_eq!(as_bool(&number), None);
+    assert!(as_obj(&number).is_none());
+    assert_eq!(f64::try_from(number.clone()), Ok(1.5));
+
+    assert_eq!(bool::try_from(Value::from(true)), Ok(true));
+    assert_eq!(String::try_from(Value::from("lox")), Ok("lox".to_string()));
+
+    let mismatch = String::try_from(number).unwrap_err();
+    assert_eq!((mismatch.expected, mismatch.found), (ValueType::Obj, ValueType::Number));
+    assert_eq!(mismatch.to_string(), "expected Obj value, found Number");
+}
+
+fn sum(args: &[Value]) -> Result<Value, String> {
+    args.iter()
+        .map(|arg| f64::try_from(arg.clone()).map_err(|_| "Arguments must be numbers.".to_string()))
+        .sum::<Result<f64, String>>()
+        .map(Value::from)
+}
+
+#[test]
+fn embedders_can_share_globals_and_call_natives() {
+    let stdout = SharedBuffer::new();
+    let mut vm = Vm::with_output(Box::new(stdout.clone()), Box::new(io::sink()));
+    vm.set_global("greeting", "hi");
+    vm.define_native("sum", sum);
+    vm.interpret("print greeting; print sum; var answer = 42;").unwrap();
+    assert_eq!(stdout.contents(), "hi\n<native fn sum>\n");
+    assert_eq!(vm.get_global("answer").map(|value| value.to_string()), Some("42".to_string()));
+    assert!(vm.get_global("missing").is_none());
+
+    let result = vm.call("sum", &[Value::from(1.0), Value::from(2.5)]).unwrap();
+    assert_eq!(f64::try_from(result), Ok(3.5));
+
+    let message = |result: Result<Value, LoxError>| match result {
+        Err(LoxError::RuntimeError(error)) => error.message,
+        other => panic!("expected a runtime error, got {:?}", other),
+    };
+    assert_eq!(message(vm.call("sum", &[Value::from(true)])), "Arguments must be numbers.");
+    assert_eq!(message(vm.call("answer", &[])), "Can only call functions and classes.");
+    assert_eq!(message(vm.call("missing", &[])), "Undefined variable 'missing'.");
+}
@@ -0,0 +1,137 @@
+use std::io;
+
+use rlox::assembler::assemble;
+use rlox::chunk::Chunk;
+use rlox::debug::disassemble_chunk;
+use rlox::{LoxError, SharedBuffer, Vm};
+
+/// Assembles `listing`, runs it and returns what it printed.
+fn run(listing: &str) -> Result<String, LoxError> {
+    let chunk = assemble(listing).unwrap();
+    let stdout = SharedBuffer::new();
+    let mut vm = Vm::with_output(Box::new(stdout.clone()), Box::new(io::sink()));
+    vm.run_chunk(chunk)?;
+    Ok(stdout.contents())
+}
+
+#[test]
+fn arithmetic_opcodes() {
+    let output = run("
+        OP_CONSTANT 0 '7'
+        OP_CONSTANT 1 '2'
+        OP_SUBTRACT
+        OP_CONSTANT 2 '3'
+        OP_MULTIPLY
+        OP_CONSTANT 1 '2'
+        OP_DIVIDE
+        OP_NEGATE
+        OP_PRINT
+        OP_RETURN
+    ");
+    assert_eq!(output.unwrap(), "-7.5\n");
+}
+
+#[test]
+fn comparison_and_logic_opcodes() {
+    let output = run("
+        OP_CONSTANT '1'
+        OP_CONSTANT '2'
+        OP_LESS
+        OP_PRINT
+        OP_CONSTANT '1'
+        OP_CONSTANT '2'
+        OP_GREATER
+        OP_PRINT
+        OP_NIL
+        OP_NOT
+        OP_PRINT
+        OP_CONSTANT \"ab\"
+        OP_CONSTANT \"a\"
+        OP_CONSTANT \"b\"
+        OP_ADD
+        OP_EQUAL
+        OP_PRINT
+        OP_TRUE
+        OP_FALSE
+        OP_EQUAL
+        OP_PRINT
+        OP_RETURN
+    ");
+    assert_eq!(output.unwrap(), "true\nfalse\ntrue\ntrue\nfalse\n");
+}
+
+#[test]
+fn global_and_string_opcodes() {
+    let output = run("
+        OP_CONSTANT 0 '2'
+        OP_DEFINE_GLOBAL 1 'n'
+        OP_CONSTANT 2 \"n is \"
+        OP_GET_GLOBAL 1 'n'
+        OP_TO_STRING
+        OP_ADD
+        OP_PRINT
+        OP_TRUE
+        OP_SET_GLOBAL 1 'n'
+        OP_POP
+        OP_GET_GLOBAL 1 'n'
+        OP_PRINT
+        OP_RETURN
+    ");
+    assert_eq!(output.unwrap(), "n is 2\ntrue\n");
+}
+
+#[test]
+fn runtime_errors_report_the_listed_line() {
+    let error = run("
+        0000    7 OP_CONSTANT         0 \"a\"
+        0002    | OP_NEGATE
+        0003    8 OP_RETURN
+    ").unwrap_err();
+    assert_eq!(error.to_string(), "Operand must be a number.\n[line 7] in script");
+}
+
+#[test]
+fn round_trips_disassembler_output() {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    let chunk = vm.compile("var a = 1.5;\nprint \"a is ${-a}\" == nil;\na = true;\nprint \"5\" + \"say \\\"hi\\\"\\n\";\n").unwrap();
+
+    let mut listing = Vec::new();
+    disassemble_chunk(&mut listing, &chunk, "<script>");
+    let listing = String::from_utf8(listing).unwrap();
+    let assembled = assemble(&listing).unwrap();
+
+    assert_eq!(assembled.code, chunk.code);
+    assert_eq!(assembled.lines, chunk.lines);
+    let constants = |chunk: &Chunk| chunk.constants.iter().map(|value| (value.value_type(), value.to_string())).collect::<Vec<_>>();
+    assert_eq!(constants(&assembled), constants(&chunk));
+
+    let mut relisted = Vec::new();
+    disassemble_chunk(&mut relisted, &assembled, "<script>");
+    assert_eq!(String::from_utf8(relisted).unwrap(), listing);
+}
+
+#[test]
+fn rejects_malformed_listings() {
+    let message = |listing: &str| assemble(listing).err().unwrap().to_string();
+    assert_eq!(message("OP_NIL\nOP_JUMP 3\n"), "[line 2] Unknown instruction 'OP_JUMP'.");
+    assert_eq!(message("OP_NIL 1\n"), "[line 1] OP_NIL takes no operand.");
+    assert_eq!(message("OP_CONSTANT 0\n"), "[line 1] Expect a quoted constant after OP_CONSTANT.");
+    assert_eq!(message("OP_CONSTANT 0 '1'\nOP_CONSTANT 0 \"1\"\n"), "[line 2] Constant 0 is already '1'.");
+    assert_eq!(message("OP_CONSTANT 'one'\n"), "[line 1] Invalid constant 'one'; write strings in double quotes.");
+    assert_eq!(message("OP_CONSTANT \"open\n"), "[line 1] Unterminated string; expect a closing \".");
+    assert_eq!(message("OP_CONSTANT '1' '2'\n"), "[line 1] Unexpected ''2'' after the operands of OP_CONSTANT.");
+    assert_eq!(message("OP_CONSTANT 1 '1'\n"), "[line 1] Constant 0 is never defined.");
+    assert_eq!(message("print 1;\n"), "[line 1] Expect an instruction.");
+}
+
+#[test]
+fn strings_that_look_like_numbers_stay_strings() {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    let chunk = vm.compile("var x = \"5\";\nprint x + \"1\";\n").unwrap();
+
+    let mut listing = Vec::new();
+    disassemble_chunk(&mut listing, &chunk, "<script>");
+    let listing = String::from_utf8(listing).unwrap();
+    assert!(listing.contains("OP_CONSTANT         1 \"5\""), "{}", listing);
+    assert_eq!(run(&listing).unwrap(), "51\n");
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rlox::ast::{Declaration, Expr, Stmt};
+use rlox::ast_parser::parse;
+use rlox::scanner::{Span, TokenType};
+use rlox::{Edition, FrontEnd, LoxError, Vm};
+
+fn compile(source: &str, front_end: FrontEnd) -> Result<String, Vec<String>> {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    vm.set_front_end(front_end);
+    match vm.compile(source) {
+        // Compared field by field, as `Chunk` has no `PartialEq`.
+        Ok(chunk) => Ok(format!("{:?} {:?} {:?} {:?} {:?}", chunk.code, chunk.lines, chunk.columns, chunk.spans, chunk.constants)),
+        Err(LoxError::CompileError(errors)) => Err(errors.iter().map(|error| format!("{:?}", error)).collect()),
+        Err(other) => panic!("unexpected error {:?}", other),
+    }
+}
+
+#[test]
+fn both_front_ends_agree_on_every_script() {
+    let files = common::lox_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"));
+    for path in files {
+        let source = fs::read_to_string(&path).unwrap();
+        assert_eq!(compile(&source, FrontEnd::Ast), compile(&source, FrontEnd::SinglePass), "{}", path.display());
+    }
+}
+
+#[test]
+fn nodes_carry_their_spans() {
+    let source = "var a = 1;\nprint -a + (2 * a);";
+    let program = parse(source, Edition::Classic).unwrap();
+    assert_eq!(program.declarations.len(), 2);
+
+    match &program.declarations[0] {
+        Declaration::Var { name, initializer: Some(Expr::Literal { .. }), span, .. } => {
+            assert_eq!(name.name, "a");
+            assert_eq!(&source[span.start..span.end], "var a = 1;");
+        }
+        other => panic!("expected a var declaration, got {:?}", other),
+    }
+
+    match &program.declarations[1] {
+        Declaration::Statement(Stmt::Print { value: Expr::Binary { left, operator, right }, span, .. }) => {
+            assert_eq!(operator.token_type, TokenType::Plus);
+            assert_eq!(&source[left.span().start..left.span().end], "-a");
+            assert_eq!(right.span(), Span { start: 22, end: 29 });
+            assert_eq!(&source[span.start..span.end], "print -a + (2 * a);");
+        }
+        other => panic!("expected a print statement, got {:?}", other),
+    }
+}
+
+#[test]
+fn reports_the_same_errors_as_the_single_pass_compiler() {
+    for source in ["print 1 +;", "var 1 = 2;\nprint (a;", "a + b = c;", "print \"${1 2}\";", "print 0x;", "print \"\\q\";"] {
+        let errors = compile(source, FrontEnd::Ast).unwrap_err();
+        assert_eq!(Err(errors), compile(source, FrontEnd::SinglePass), "{}", source);
+    }
+}
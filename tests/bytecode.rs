@@ -0,0 +1,60 @@
+use std::io;
+
+use rlox::bytecode::{deserialize, serialize, FORMAT_VERSION, MAGIC};
+use rlox::chunk::Chunk;
+use rlox::{SharedBuffer, Vm};
+
+fn compile(source: &str) -> Chunk {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    vm.compile(source).unwrap()
+}
+
+fn run(chunk: Chunk) -> String {
+    let stdout = SharedBuffer::new();
+    let mut vm = Vm::with_output(Box::new(stdout.clone()), Box::new(io::sink()));
+    vm.run_chunk(chunk).unwrap();
+    stdout.contents()
+}
+
+const SOURCE: &str = "var name = \"wörld\";\nprint \"hello ${name}\";\nprint 1.5 * 2 == 3;\n";
+
+#[test]
+fn round_trips_a_chunk() {
+    let chunk = compile(SOURCE);
+    let loaded = deserialize(&serialize(&chunk).unwrap()).unwrap();
+
+    assert_eq!(loaded.code, chunk.code);
+    assert_eq!(loaded.lines, chunk.lines);
+    assert_eq!(loaded.columns, chunk.columns);
+    assert_eq!(loaded.constants.len(), chunk.constants.len());
+    assert_eq!(run(loaded), run(chunk));
+}
+
+#[test]
+fn rejects_every_truncation() {
+    let bytes = serialize(&compile(SOURCE)).unwrap();
+    for length in 0..bytes.len() {
+        let error = deserialize(&bytes[..length]).err().unwrap();
+        assert_eq!(error.message, "Truncated bytecode file.", "at length {}", length);
+    }
+}
+
+#[test]
+fn rejects_other_versions_and_formats() {
+    let mut bytes = serialize(&compile(SOURCE)).unwrap();
+    bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        deserialize(&bytes).err().unwrap().message,
+        format!("Unsupported bytecode version {} (expected {}).", FORMAT_VERSION + 1, FORMAT_VERSION),
+    );
+
+    // Version 1 files predate the opcodes added for the peephole pass.
+    bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&1u16.to_le_bytes());
+    assert_eq!(deserialize(&bytes).err().unwrap().message, "Unsupported bytecode version 1 (expected 2).");
+
+    assert_eq!(deserialize(b"print 1;\n").err().unwrap().message, "Not an rlox bytecode file.");
+
+    let mut bytes = serialize(&compile(SOURCE)).unwrap();
+    bytes.push(0);
+    assert_eq!(deserialize(&bytes).err().unwrap().message, "Unexpected data after the end of the bytecode.");
+}
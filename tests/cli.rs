@@ -0,0 +1,141 @@
+//! Runs the `rlox` binary the way a user would.
+
+use std::fs;
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Writes `source` to a scratch file named `name` and returns its path.
+fn script(name: &str, source: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join(name);
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox")).args(args).output().unwrap()
+}
+
+#[test]
+fn repl_prints_bare_expressions() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"1 + 2\nvar a = 3;\na = a * 2\nprint a;\nprint a\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "> 3\n> > 6\n> 6\n> > \n");
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with("[line 2] Error at end: Expect ';' after value.\n"));
+}
+
+#[test]
+fn tokens_lists_every_token() {
+    let path = script("tokens.lox", "(1 + x)\n");
+    let output = rlox(&["--tokens", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), vec![
+        "     1:1 LeftParen      '('",
+        "     1:2 Number         '1'",
+        "     1:4 Plus           '+'",
+        "     1:6 Identifier     'x'",
+        "     1:7 RightParen     ')'",
+        "     2:1 EOF            ''",
+    ]);
+}
+
+#[test]
+fn tokens_fails_on_error_tokens() {
+    let path = script("token_error.lox", "1 # 2");
+    let output = rlox(&["--tokens", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1:3 Error          '#' Unexpected character."), "{}", stdout);
+    assert!(stdout.contains("1:5 Number         '2'"), "{}", stdout);
+}
+
+#[test]
+fn disassemble_lists_the_script_without_running_it() {
+    let path = script("disassemble.lox", "var a = 1;\nprint -a;\n");
+    for front_end in ["--front-end=single-pass", "--front-end=ast"] {
+        let output = rlox(&["--disassemble", front_end, path.to_str().unwrap()]);
+
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.lines().collect::<Vec<_>>(), vec![
+            "== <script> ==",
+            "0000    1 OP_CONSTANT         1 '1'",
+            "0002    | OP_DEFINE_GLOBAL    0 \"a\"",
+            "0004    2 OP_GET_GLOBAL       0 \"a\"",
+            "0006    | OP_NEGATE",
+            "0007    | OP_PRINT",
+            "0008    3 OP_RETURN",
+        ], "{}", front_end);
+    }
+}
+
+#[test]
+fn disassemble_reports_compile_errors() {
+    let path = script("disassemble_error.lox", "print 1 +;\n");
+    let output = rlox(&["--disassemble", "--diagnostics=short", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "[line 1] Error at ';': Expect expression.\n");
+}
+
+#[test]
+fn compiled_scripts_run_without_their_source() {
+    let source = script("compiled.lox", "var a = 2;\nprint \"a = ${a * 21}\";\n");
+    let compiled = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compiled.loxc");
+    let _ = fs::remove_file(&compiled);
+
+    let output = rlox(&["compile", source.to_str().unwrap(), "-o", compiled.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    fs::remove_file(&source).unwrap();
+
+    let output = rlox(&[compiled.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a = 42\n");
+}
+
+#[test]
+fn loading_a_truncated_file_fails_cleanly() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("truncated.loxc");
+    fs::write(&path, b"LOXC\x01").unwrap();
+
+    let output = rlox(&[path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with("Truncated bytecode file.\n"));
+}
+
+#[test]
+fn fmt_rewrites_files_and_check_reports_them() {
+    let path = script("fmt.lox", "{\n    print 1+2;\n}");
+    let path = path.to_str().unwrap();
+
+    let output = rlox(&["fmt", "--check", path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), format!("Would reformat '{}'\n", path));
+    assert_eq!(fs::read_to_string(path).unwrap(), "{\n    print 1+2;\n}");
+
+    assert_eq!(rlox(&["fmt", path]).status.code(), Some(0));
+    assert_eq!(fs::read_to_string(path).unwrap(), "{\n  print 1 + 2;\n}\n");
+    assert_eq!(rlox(&["fmt", "--check", path]).status.code(), Some(0));
+}
+
+#[test]
+fn fmt_leaves_files_with_scan_errors_alone() {
+    let path = script("fmt_error.lox", "print \"open;\n");
+    let output = rlox(&["fmt", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with("Error: Unterminated string.\n"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "print \"open;\n");
+}
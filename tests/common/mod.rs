@@ -0,0 +1,130 @@
+//! Expectation parsing and checking shared by the script-driven test
+//! runners. Scripts describe their expected behaviour in comments, following
+//! the craftinginterpreters conventions:
+//!
+//! * `// expect: <output>` - a line the script prints.
+//! * `// expect runtime error: <message>` - the script fails at runtime on
+//!   this line with `<message>`.
+//! * `// Error at '<lexeme>': <message>` - a compile error on this line.
+//! * `// [line N] Error ...` - a compile error reported on line N. The
+//!   `[c line N]` form is accepted too and `[java line N]` is ignored.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const EXIT_COMPILE_ERROR: i32 = 65;
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+
+pub struct Expectations {
+    pub output: Vec<String>,
+    pub compile_errors: Vec<String>,
+    pub runtime_error: Option<(String, usize)>,
+}
+
+impl Expectations {
+    pub fn exit_code(&self) -> i32 {
+        if self.runtime_error.is_some() { EXIT_RUNTIME_ERROR }
+        else if !self.compile_errors.is_empty() { EXIT_COMPILE_ERROR }
+        else { 0 }
+    }
+}
+
+pub fn parse_expectations(source: &str) -> Expectations {
+    let mut expectations = Expectations {
+        output: Vec::new(),
+        compile_errors: Vec::new(),
+        runtime_error: None,
+    };
+
+    for (idx, line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+
+        if let Some(output) = after(line, "// expect: ") {
+            expectations.output.push(output.to_string());
+        }
+        else if let Some(message) = after(line, "// expect runtime error: ") {
+            expectations.runtime_error = Some((message.to_string(), line_number));
+        }
+        else if let Some(error) = after(line, "// [c line ") {
+            expectations.compile_errors.push(format!("[line {}", error));
+        }
+        else if let Some(error) = after(line, "// [line ").filter(|error| error.contains("] Error")) {
+            expectations.compile_errors.push(format!("[line {}", error));
+        }
+        else if let Some(error) = after(line, "// Error") {
+            expectations.compile_errors.push(format!("[line {}] Error{}", line_number, error));
+        }
+    }
+
+    expectations
+}
+
+/// The rest of `line` after the first occurrence of `marker`.
+fn after<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    line.find(marker).map(|start| &line[start + marker.len()..])
+}
+
+fn diff(label: &str, expected: &[String], actual: &[String]) -> Option<String> {
+    if expected == actual { return None; }
+
+    let mut report = format!("  {} differs:\n", label);
+    for line in expected {
+        report.push_str(&format!("    - {}\n", line));
+    }
+    for line in actual {
+        report.push_str(&format!("    + {}\n", line));
+    }
+    Some(report)
+}
+
+/// Compares a finished run against `expectations`, returning a report of
+/// every mismatch.
+pub fn check(expectations: &Expectations, stdout: &str, stderr: &str, exit_code: i32) -> Result<(), String> {
+    let stdout: Vec<String> = stdout.lines().map(str::to_string).collect();
+    let stderr: Vec<String> = stderr.lines().map(str::to_string).collect();
+    let mut failures = Vec::new();
+
+    failures.extend(diff("stdout", &expectations.output, &stdout));
+
+    if let Some((message, line)) = &expectations.runtime_error {
+        let trace = format!("[line {}]", line);
+        let matches = stderr.first() == Some(message)
+            && stderr.get(1).is_some_and(|frame| frame.starts_with(&trace));
+        if !matches {
+            failures.extend(diff("runtime error", &[message.clone(), trace], &stderr));
+        }
+    }
+    else {
+        failures.extend(diff("compile errors", &expectations.compile_errors, &stderr));
+    }
+
+    if exit_code != expectations.exit_code() {
+        failures.push(format!("  expected exit code {}, got {}\n", expectations.exit_code(), exit_code));
+    }
+
+    if failures.is_empty() { Ok(()) } else { Err(failures.concat()) }
+}
+
+/// Every `.lox` file under `dir`, in path order.
+pub fn lox_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_lox_files(dir, &mut files);
+    files
+}
+
+fn collect_lox_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_lox_files(&path, files);
+        }
+        else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+}
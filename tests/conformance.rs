@@ -0,0 +1,124 @@
+//! Runs the vendored Crafting Interpreters test corpus under
+//! `tests/conformance` against the `rlox` binary and reports how many
+//! scripts pass in each chapter. Most of the corpus exercises features the
+//! VM doesn't have yet, so this is a progress report rather than a gate:
+//!
+//!     cargo test --test conformance -- --ignored --nocapture
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Token-dump tests for the tree-walking interpreter; clox doesn't run them.
+const SKIPPED_CHAPTERS: &[&str] = &["scanning"];
+
+/// Upstream scripts that aren't vendored, with the limit each one hits.
+/// They are generated, and their expectations name the line of the token
+/// that trips the limit, so only the upstream files themselves will do. The
+/// report lists them so its totals aren't read as the whole corpus.
+const NOT_VENDORED: &[(&str, &str)] = &[
+    ("limit/loop_too_large.lox", "a loop body longer than a jump can span"),
+    ("limit/too_many_upvalues.lox", "more than 256 upvalues in one closure"),
+    ("function/too_many_arguments.lox", "more than 255 arguments in a call"),
+    ("function/too_many_parameters.lox", "more than 255 parameters in a function"),
+    ("method/too_many_arguments.lox", "more than 255 arguments in a method call"),
+    ("method/too_many_parameters.lox", "more than 255 parameters in a method"),
+];
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+type ScriptResult = (PathBuf, Result<(), String>);
+
+fn run(path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let expectations = common::parse_expectations(&source);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| error.to_string())?;
+
+    // Drained while the child runs, so a full pipe can't block it.
+    let stdout = drain(child.stdout.take().unwrap());
+    let stderr = drain(child.stderr.take().unwrap());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|error| error.to_string())? {
+            break status;
+        }
+        if started.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("  timed out after {:?}\n", TIMEOUT));
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    let stdout = stdout.join().unwrap().map_err(|error| error.to_string())?;
+    let stderr = stderr.join().unwrap().map_err(|error| error.to_string())?;
+
+    // A signal (e.g. from a panic-triggered abort) leaves no exit code.
+    let exit_code = status.code().unwrap_or(-1);
+    common::check(&expectations, &stdout, &stderr, exit_code)
+}
+
+fn drain(mut pipe: impl Read + Send + 'static) -> JoinHandle<io::Result<String>> {
+    thread::spawn(move || {
+        let mut output = String::new();
+        pipe.read_to_string(&mut output)?;
+        Ok(output)
+    })
+}
+
+fn chapter(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap();
+    let first = relative.components().next().unwrap().as_os_str().to_string_lossy();
+    first.trim_end_matches(".lox").to_string()
+}
+
+#[test]
+#[ignore]
+fn conformance_report() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut chapters: BTreeMap<String, Vec<ScriptResult>> = BTreeMap::new();
+
+    for path in common::lox_files(&root) {
+        let name = chapter(&root, &path);
+        if SKIPPED_CHAPTERS.contains(&name.as_str()) { continue; }
+        let result = run(&path);
+        chapters.entry(name).or_default().push((path, result));
+    }
+
+    let mut passed = 0;
+    let mut total = 0;
+    println!();
+    for (name, results) in &chapters {
+        let chapter_passed = results.iter().filter(|(_, result)| result.is_ok()).count();
+        println!("{:24} {:4} / {:<4}", name, chapter_passed, results.len());
+        passed += chapter_passed;
+        total += results.len();
+    }
+    println!("{:24} {:4} / {:<4}", "total", passed, total);
+
+    println!("\nnot vendored:");
+    for (path, reason) in NOT_VENDORED {
+        println!("  {:36} {}", path, reason);
+    }
+
+    if std::env::var_os("CONFORMANCE_VERBOSE").is_some() {
+        for (path, result) in chapters.values().flatten() {
+            if let Err(report) = result {
+                println!("\n{}\n{}", path.strip_prefix(&root).unwrap().display(), report);
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::io;
+
+use rlox::chunk::{add_constant, init_chunk};
+use rlox::{Value, Vm};
+
+#[test]
+fn repeated_numbers_and_strings_share_a_slot() {
+    let mut chunk = init_chunk();
+    assert_eq!(add_constant(&mut chunk, Value::from(1.5)), 0);
+    assert_eq!(add_constant(&mut chunk, Value::from("name")), 1);
+    assert_eq!(add_constant(&mut chunk, Value::from(1.5)), 0);
+    assert_eq!(add_constant(&mut chunk, Value::from("name")), 1);
+    assert_eq!(add_constant(&mut chunk, Value::from("1.5")), 2);
+    assert_eq!(chunk.constants.len(), 3);
+}
+
+#[test]
+fn signed_zeros_and_nans_are_compared_by_bits() {
+    let mut chunk = init_chunk();
+    assert_eq!(add_constant(&mut chunk, Value::from(0.0)), 0);
+    assert_eq!(add_constant(&mut chunk, Value::from(-0.0)), 1);
+    assert_eq!(add_constant(&mut chunk, Value::from(f64::NAN)), 2);
+    assert_eq!(add_constant(&mut chunk, Value::from(f64::NAN)), 2);
+    assert_eq!(add_constant(&mut chunk, Value::from(-f64::NAN)), 3);
+}
+
+#[test]
+fn repeated_literals_stay_under_the_constant_limit() {
+    let source: String = (0..300).map(|idx| format!("var v{} = \"same\" + count * {};\n", idx % 100, idx % 50)).collect();
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    let chunk = vm.compile(&source).unwrap();
+    assert_eq!(chunk.constants.len(), 100 + 2 + 50);
+}
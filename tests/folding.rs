@@ -0,0 +1,55 @@
+use std::io;
+
+use rlox::chunk::{Chunk, OP_ADD, OP_CONSTANT, OP_FALSE, OP_GET_GLOBAL, OP_NEGATE, OP_PRINT, OP_RETURN, OP_TRUE};
+use rlox::{run_captured, Vm};
+
+fn compile(source: &str) -> Chunk {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    vm.compile(source).unwrap()
+}
+
+fn constants(chunk: &Chunk) -> Vec<String> {
+    chunk.constants.iter().map(|value| value.to_string()).collect()
+}
+
+#[test]
+fn folds_arithmetic_into_one_constant() {
+    let source = "print -(1 + 2) * 3 / 2 - 0.5;";
+    let chunk = compile(source);
+    assert_eq!(chunk.code, vec![OP_CONSTANT, 0, OP_PRINT, OP_RETURN]);
+    assert_eq!(constants(&chunk), vec!["-5"]);
+    assert_eq!(run_captured(source).stdout, "-5\n");
+}
+
+#[test]
+fn folds_comparisons_not_and_concatenation() {
+    let chunk = compile("print !(1 >= 2) == (\"a\" + \"b\" != \"ab\");");
+    assert_eq!(chunk.code, vec![OP_FALSE, OP_PRINT, OP_RETURN]);
+    assert!(chunk.constants.is_empty());
+
+    let chunk = compile("print !nil;");
+    assert_eq!(chunk.code, vec![OP_TRUE, OP_PRINT, OP_RETURN]);
+
+    let source = "print \"con\" + \"cat\" + \"enate\";";
+    let chunk = compile(source);
+    assert_eq!(chunk.code, vec![OP_CONSTANT, 0, OP_PRINT, OP_RETURN]);
+    assert_eq!(constants(&chunk), vec!["concatenate"]);
+    assert_eq!(run_captured(source).stdout, "concatenate\n");
+}
+
+#[test]
+fn leaves_variables_alone() {
+    let chunk = compile("print 2 * 3 + a;");
+    assert_eq!(chunk.code, vec![OP_CONSTANT, 0, OP_GET_GLOBAL, 1, OP_ADD, OP_PRINT, OP_RETURN]);
+    assert_eq!(constants(&chunk), vec!["6", "a"]);
+}
+
+#[test]
+fn ill_typed_constants_still_fail_at_run_time() {
+    let chunk = compile("print -\"s\";");
+    assert_eq!(chunk.code, vec![OP_CONSTANT, 0, OP_NEGATE, OP_PRINT, OP_RETURN]);
+
+    let run = run_captured("print 1;\nprint 1 + (2 < \"3\");");
+    assert_eq!(run.stdout, "1\n");
+    assert_eq!(run.stderr, "Operands must be numbers.\n[line 2] in script\n");
+}
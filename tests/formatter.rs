@@ -0,0 +1,73 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use rlox::formatter::format_source;
+use rlox::Edition;
+
+fn format(source: &str) -> String {
+    format_source(source, Edition::Classic).unwrap()
+}
+
+#[test]
+fn normalizes_spacing_between_tokens() {
+    assert_eq!(format("var x=-1;print(x+1)*-x ;"), "var x = -1; print (x + 1) * -x;\n");
+    assert_eq!(format("fun f(a,b){return !f(a).b-2;}"), "fun f(a, b) { return !f(a).b - 2; }\n");
+    assert_eq!(format("for(;;){}"), "for (;;) {}\n");
+    assert_eq!(format("print \"a ${ x+1 } b ${-x}\";"), "print \"a ${x + 1} b ${-x}\";\n");
+}
+
+#[test]
+fn indents_blocks_and_continuation_lines() {
+    let source = "\n\nclass A {\n        f() {\n    if (x)\nprint 1;\nprint 1 +\n  2 +\n 3;\n}\n    }\n\n\n\nA().f();";
+    assert_eq!(format(source), "\
+class A {
+  f() {
+    if (x)
+      print 1;
+    print 1 +
+      2 +
+      3;
+  }
+}
+
+A().f();
+");
+}
+
+#[test]
+fn keeps_comments() {
+    let source = "print 1;   // one   \n{\n/* two\n     lines */\n    print 2; /* inline */ }\n";
+    assert_eq!(format(source), "print 1; // one\n{\n  /* two\n     lines */\n  print 2; /* inline */ }\n");
+}
+
+#[test]
+fn formatting_is_idempotent() {
+    let files = common::lox_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"));
+    for path in files {
+        // Scripts testing scanner errors can't be formatted.
+        let once = match format_source(&fs::read_to_string(&path).unwrap(), Edition::Classic) {
+            Ok(once) => once,
+            Err(_) => continue,
+        };
+        assert_eq!(format(&once), once, "{}", path.display());
+    }
+}
+
+#[test]
+fn example_programs_are_formatted() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let source = fs::read_to_string(&path).unwrap();
+        assert_eq!(format(&source), source, "{} is not formatted; run `rlox fmt` on it", path.display());
+    }
+}
+
+#[test]
+fn scan_errors_are_reported() {
+    let errors = format_source("print \"open;\n", Edition::Classic).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "Unterminated string.");
+}
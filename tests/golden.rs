@@ -0,0 +1,35 @@
+//! Runs every script under `tests/lox` in-process and checks its output
+//! against the expectations embedded in its comments.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rlox::{run_captured, LoxError};
+
+use common::{EXIT_COMPILE_ERROR, EXIT_RUNTIME_ERROR};
+
+fn run(path: &PathBuf) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let expectations = common::parse_expectations(&source);
+    let run = run_captured(&source);
+    let exit_code = match run.result {
+        Ok(()) => 0,
+        Err(LoxError::CompileError(_) | LoxError::BytecodeError(_)) => EXIT_COMPILE_ERROR,
+        Err(LoxError::RuntimeError(_)) => EXIT_RUNTIME_ERROR,
+    };
+    common::check(&expectations, &run.stdout, &run.stderr, exit_code)
+}
+
+#[test]
+fn golden_files() {
+    let files = common::lox_files(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"));
+    assert!(!files.is_empty(), "no .lox files found under tests/lox");
+
+    let failures: Vec<String> = files.iter()
+        .filter_map(|path| run(path).err().map(|report| format!("{}\n{}", path.display(), report)))
+        .collect();
+
+    assert!(failures.is_empty(), "{} of {} scripts failed:\n\n{}", failures.len(), files.len(), failures.join("\n"));
+}
@@ -0,0 +1,71 @@
+use rlox::scanner::{Scanner, Span, Token, TokenType};
+
+fn tokens(source: &str, lossless: bool) -> Vec<Token> {
+    let mut scanner = Scanner::new(source.to_string());
+    scanner.set_lossless(lossless);
+    scanner.collect()
+}
+
+#[test]
+fn yields_tokens_with_text_and_spans() {
+    let source = "1 + \"é\" // sum";
+    let tokens = tokens(source, false);
+    let summary: Vec<(TokenType, &str)> = tokens.iter()
+        .map(|token| (token.token_type, token.text(source)))
+        .collect();
+
+    assert_eq!(summary, vec![
+        (TokenType::Number, "1"),
+        (TokenType::Plus, "+"),
+        (TokenType::String, "\"é\""),
+        (TokenType::EOF, ""),
+    ]);
+    assert_eq!(tokens[2].span, Span { start: 4, end: 8 });
+    assert_eq!(tokens[2].column, 5);
+}
+
+#[test]
+fn columns_count_from_the_start_of_each_line() {
+    let source = "print \"a\nb\" + x;\n  y";
+    let positions: Vec<(&str, i32, usize)> = tokens(source, false).iter()
+        .map(|token| (token.text(source), token.line, token.column))
+        .collect();
+    assert_eq!(positions, vec![
+        ("print", 1, 1),
+        // A multi-line token reports the line and column it starts at.
+        ("\"a\nb\"", 1, 7),
+        ("+", 2, 4),
+        ("x", 2, 6),
+        (";", 2, 7),
+        ("y", 3, 3),
+        ("", 3, 4),
+    ]);
+}
+
+#[test]
+fn lossless_mode_keeps_comments_and_whitespace() {
+    let source = "/* a /* nested */ one */ 1 +\n\t2 // two\n";
+    let tokens = tokens(source, true);
+
+    let text: String = tokens.iter().map(|token| token.text(source)).collect();
+    assert_eq!(text, source);
+
+    let types: Vec<TokenType> = tokens.iter().map(|token| token.token_type).collect();
+    assert_eq!(types, vec![
+        TokenType::Comment, TokenType::Whitespace, TokenType::Number,
+        TokenType::Whitespace, TokenType::Plus, TokenType::Whitespace,
+        TokenType::Number, TokenType::Whitespace, TokenType::Comment,
+        TokenType::Whitespace, TokenType::EOF,
+    ]);
+    assert_eq!(tokens[6].line, 2);
+}
+
+#[test]
+fn errors_are_tokens_too() {
+    let source = "1 @ 2";
+    let tokens = tokens(source, false);
+    assert_eq!(tokens[1].token_type, TokenType::Error);
+    assert_eq!(tokens[1].message, "Unexpected character.");
+    assert_eq!(tokens[1].text(source), "@");
+    assert_eq!(tokens.last().map(|token| token.token_type), Some(TokenType::EOF));
+}
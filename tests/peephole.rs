@@ -0,0 +1,72 @@
+use std::io;
+
+use rlox::assembler::assemble;
+use rlox::chunk::{Chunk, OP_CONSTANT, OP_GET_GLOBAL, OP_GET_GLOBAL_ADD_CONSTANT, OP_GREATER_EQUAL, OP_LESS, OP_LESS_EQUAL, OP_NOT_EQUAL, OP_PRINT, OP_RETURN};
+use rlox::debug::disassemble_chunk;
+use rlox::peephole::optimize;
+use rlox::{run_captured, LoxError, Vm};
+
+fn compile(source: &str) -> Chunk {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    vm.compile(source).unwrap()
+}
+
+#[test]
+fn comparisons_get_dedicated_opcodes() {
+    assert_eq!(compile("print a != 1;").code, vec![OP_GET_GLOBAL, 0, OP_CONSTANT, 1, OP_NOT_EQUAL, OP_PRINT, OP_RETURN]);
+    assert_eq!(compile("print a >= 1;").code, vec![OP_GET_GLOBAL, 0, OP_CONSTANT, 1, OP_GREATER_EQUAL, OP_PRINT, OP_RETURN]);
+    assert_eq!(compile("print !(a > 1);").code, vec![OP_GET_GLOBAL, 0, OP_CONSTANT, 1, OP_LESS_EQUAL, OP_PRINT, OP_RETURN]);
+    assert_eq!(compile("print !!(a < 1);").code, vec![OP_GET_GLOBAL, 0, OP_CONSTANT, 1, OP_LESS, OP_PRINT, OP_RETURN]);
+
+    let output = run_captured("var a = 2; print a != 2; print a >= 2; print a <= 1; print !(a < 3);");
+    assert_eq!(output.stdout, "false\ntrue\nfalse\nfalse\n");
+}
+
+#[test]
+fn global_plus_constant_is_one_instruction() {
+    let chunk = compile("print a + 1;");
+    assert_eq!(chunk.code, vec![OP_GET_GLOBAL_ADD_CONSTANT, 0, 1, OP_PRINT, OP_RETURN]);
+    assert_eq!(chunk.code.len(), chunk.lines.len());
+
+    let mut listing = Vec::new();
+    disassemble_chunk(&mut listing, &chunk, "<script>");
+    let listing = String::from_utf8(listing).unwrap();
+    assert!(listing.contains("OP_GET_GLOBAL_ADD_CONSTANT    0 \"a\"    1 '1'"), "{}", listing);
+    assert_eq!(assemble(&listing).unwrap().code, chunk.code);
+
+    assert_eq!(run_captured("var a = \"x\"; print a + \"y\";").stdout, "xy\n");
+}
+
+#[test]
+fn fused_instructions_report_errors_where_they_did() {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    match vm.interpret("var a = true;\nprint a\n  + 1;") {
+        Err(LoxError::RuntimeError(error)) => {
+            assert_eq!(error.message, "Operands must be two numbers or two strings.");
+            assert_eq!((error.stack_trace[0].line, error.stack_trace[0].column), (3, 3));
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+    match vm.interpret("print b + 1;") {
+        Err(LoxError::RuntimeError(error)) => {
+            assert_eq!(error.message, "Undefined variable 'b'.");
+            assert_eq!((error.stack_trace[0].line, error.stack_trace[0].column), (1, 7));
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn drops_code_after_a_return() {
+    let mut chunk = assemble("
+        OP_CONSTANT 0 '1'
+        OP_PRINT
+        OP_RETURN
+        OP_CONSTANT 1 '2'
+        OP_PRINT
+        OP_RETURN
+    ").unwrap();
+    optimize(&mut chunk);
+    assert_eq!(chunk.code, vec![OP_CONSTANT, 0, OP_PRINT, OP_RETURN]);
+    assert_eq!((chunk.lines.len(), chunk.columns.len(), chunk.spans.len()), (4, 4, 4));
+}
@@ -0,0 +1,60 @@
+use std::io;
+
+use rlox::bytecode::{deserialize, serialize};
+use rlox::chunk::{add_constant, init_chunk, write_chunk, Chunk, OP_ADD, OP_CONSTANT, OP_GET_GLOBAL, OP_NIL, OP_POP, OP_PRINT, OP_RETURN};
+use rlox::scanner::Span;
+use rlox::verifier::verify_chunk;
+use rlox::{LoxError, Value, Vm};
+
+fn chunk(code: &[usize], constants: &[Value]) -> Chunk {
+    let mut chunk = init_chunk();
+    for constant in constants {
+        add_constant(&mut chunk, constant.clone());
+    }
+    for byte in code {
+        write_chunk(&mut chunk, *byte, 1, 1, Span::default());
+    }
+    chunk
+}
+
+fn verify(code: &[usize], constants: &[Value]) -> Result<(), String> {
+    verify_chunk(&chunk(code, constants)).map_err(|error| error.message)
+}
+
+#[test]
+fn accepts_compiled_code() {
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    let chunk = vm.compile("var a = 1;\nprint \"${a} + 2 = ${a + 2}\";\na = !a;\n").unwrap();
+    assert_eq!(verify_chunk(&chunk), Ok(()));
+}
+
+#[test]
+fn rejects_malformed_code() {
+    assert_eq!(verify(&[OP_NIL, 200, OP_RETURN], &[]), Err("Invalid bytecode at offset 1: unknown opcode 200.".to_string()));
+    assert_eq!(verify(&[OP_CONSTANT, 1, OP_RETURN], &[Value::from(1.0)]), Err("Invalid bytecode at offset 0: constant 1 is out of bounds.".to_string()));
+    assert_eq!(verify(&[OP_CONSTANT], &[Value::from(1.0)]), Err("Invalid bytecode at offset 0: missing operand.".to_string()));
+    assert_eq!(verify(&[OP_GET_GLOBAL, 0, OP_RETURN], &[Value::from(1.0)]), Err("Invalid bytecode at offset 0: constant 0 is not a variable name.".to_string()));
+    assert_eq!(verify(&[OP_CONSTANT, 0, OP_ADD, OP_RETURN], &[Value::from(1.0)]), Err("Invalid bytecode at offset 2: stack underflow.".to_string()));
+    assert_eq!(verify(&[OP_NIL, OP_PRINT], &[]), Err("Invalid bytecode at offset 2: execution runs past the end of the code.".to_string()));
+    assert_eq!(verify(&[], &[]), Err("Invalid bytecode at offset 0: execution runs past the end of the code.".to_string()));
+    assert_eq!(verify(&[OP_NIL, OP_POP, OP_RETURN], &[]), Ok(()));
+}
+
+#[test]
+fn rejects_missing_debug_info() {
+    let mut chunk = chunk(&[OP_NIL, OP_RETURN], &[]);
+    chunk.lines.pop();
+    assert_eq!(verify_chunk(&chunk).err().unwrap().message, "Debug info doesn't cover every byte of code.");
+}
+
+#[test]
+fn loaded_chunks_are_verified_before_running() {
+    // Decoding only checks the file's structure; run_chunk verifies.
+    let loaded = deserialize(&serialize(&chunk(&[OP_POP, OP_RETURN], &[])).unwrap()).unwrap();
+
+    let mut vm = Vm::with_output(Box::new(io::sink()), Box::new(io::sink()));
+    match vm.run_chunk(loaded) {
+        Err(LoxError::BytecodeError(error)) => assert_eq!(error.message, "Invalid bytecode at offset 0: stack underflow."),
+        other => panic!("expected the chunk to be rejected, got {:?}", other),
+    }
+}